@@ -29,6 +29,18 @@ fn main() {
 	println!("cargo:rerun-if-changed=tc-frontend/svelte.config.js");
 	println!("cargo:rerun-if-changed=tc-frontend/vite.config.js");
 	println!("cargo:rerun-if-changed=migrations");
+	println!("cargo:rerun-if-changed=proto");
+
+	// the CodeCTRL protocol is compiled with a bundled protoc, so that it doesn't have to
+	// be installed to build the backend.
+	let protoc = protoc_bin_vendored::protoc_bin_path()
+		.expect("Could not find the bundled protoc for this platform");
+	env::set_var("PROTOC", protoc);
+
+	tonic_build::configure()
+		.build_client(false)
+		.compile(&["proto/codectrl/logs_service.proto"], &["proto"])
+		.expect("Could not compile the CodeCTRL protocol");
 
 	let Some(package_manager) = KNOWN_PACKAGE_MANAGERS
 		.iter()
@@ -0,0 +1,55 @@
+-- Allow backtraces to hold many layers in their original order, and share identical
+-- layers between backtraces rather than storing the same frame once per log.
+
+CREATE FUNCTION layer_fingerprint(
+  "name" text,
+  "file_path" text,
+  "code" text,
+  "line_number" int,
+  "column_number" int
+) RETURNS text
+LANGUAGE sql IMMUTABLE PARALLEL SAFE
+AS $$
+  SELECT md5(
+    "name" || E'\x1f' || "file_path" || E'\x1f' || "code" || E'\x1f' ||
+    "line_number"::text || E'\x1f' || "column_number"::text
+  )
+$$;
+
+ALTER TABLE "Layers" ADD COLUMN "fingerprint" text NOT NULL GENERATED ALWAYS AS (
+  layer_fingerprint("name", "file_path", "code", "line_number", "column_number")
+) STORED;
+
+CREATE TABLE "OrderedBacktracesLayers" (
+  "backtrace_id" int NOT NULL,
+  "position" int NOT NULL,
+  "layer_id" int NOT NULL,
+  PRIMARY KEY ("backtrace_id", "position")
+);
+
+-- the previous schema could only ever hold a single layer per backtrace, so every
+-- existing layer is the first frame. duplicate layers are replaced by the oldest copy.
+INSERT INTO "OrderedBacktracesLayers" ("backtrace_id", "position", "layer_id")
+SELECT "BacktracesLayers"."backtrace_id", 0, "Kept"."id"
+FROM "BacktracesLayers"
+JOIN "Layers" ON "Layers"."id" = "BacktracesLayers"."layer_id"
+JOIN (
+  SELECT min("id") AS "id", "fingerprint" FROM "Layers" GROUP BY "fingerprint"
+) AS "Kept" ON "Kept"."fingerprint" = "Layers"."fingerprint";
+
+DROP TABLE "BacktracesLayers";
+
+ALTER TABLE "OrderedBacktracesLayers" RENAME TO "BacktracesLayers";
+
+ALTER TABLE "BacktracesLayers" RENAME CONSTRAINT "OrderedBacktracesLayers_pkey" TO "BacktracesLayers_pkey";
+
+DELETE FROM "Layers"
+WHERE "id" NOT IN (SELECT min("id") FROM "Layers" GROUP BY "fingerprint");
+
+ALTER TABLE "Layers" ADD UNIQUE ("fingerprint");
+
+CREATE INDEX ON "BacktracesLayers" ("layer_id");
+
+ALTER TABLE "BacktracesLayers" ADD FOREIGN KEY ("backtrace_id") REFERENCES "Backtraces" ("id");
+
+ALTER TABLE "BacktracesLayers" ADD FOREIGN KEY ("layer_id") REFERENCES "Layers" ("id");
@@ -0,0 +1,6 @@
+-- Support filtering and paginating logs by date, both across the whole server and for
+-- a single client, without having to sort the whole table.
+
+CREATE INDEX ON "Logs" ("date", "id");
+
+CREATE INDEX ON "Logs" ("client_id", "date", "id");
@@ -0,0 +1,19 @@
+-- Full-text search over log messages, the code in their snippets, and the names and
+-- code of the layers in their backtraces.
+
+ALTER TABLE "Logs" ADD COLUMN "search" tsvector NOT NULL GENERATED ALWAYS AS (
+  setweight(to_tsvector('english', "message"), 'A') ||
+  setweight(json_to_tsvector('english', "snippet", '["string"]'), 'B')
+) STORED;
+
+ALTER TABLE "Layers" ADD COLUMN "search" tsvector NOT NULL GENERATED ALWAYS AS (
+  setweight(to_tsvector('english', "name"), 'A') ||
+  setweight(to_tsvector('english', "code"), 'B')
+) STORED;
+
+CREATE INDEX ON "Logs" USING GIN ("search");
+
+CREATE INDEX ON "Layers" USING GIN ("search");
+
+-- used to find the logs that a matching layer belongs to.
+CREATE INDEX ON "Logs" ("backtrace_id");
@@ -0,0 +1,16 @@
+-- The API keys that clients send their logs with. Only a SHA-256 hash of each key is
+-- stored, along with its first few characters so that it can be recognised.
+
+CREATE TABLE "ApiKeys" (
+  "id" uuid PRIMARY KEY DEFAULT (uuid_generate_v4()),
+  "client_id" int NOT NULL,
+  "prefix" text NOT NULL,
+  "key_hash" text NOT NULL UNIQUE,
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "last_used" timestamp,
+  "revoked_at" timestamp
+);
+
+ALTER TABLE "ApiKeys" ADD FOREIGN KEY ("client_id") REFERENCES "Clients" ("id") ON DELETE CASCADE;
+
+CREATE INDEX ON "ApiKeys" ("client_id");
@@ -0,0 +1,41 @@
+-- Identifies clients by a random UUID instead of a sequential integer, so that the ID
+-- of one client can't be guessed from another. Every existing client is given a new ID,
+-- and the logs and API keys that belong to it are moved over to that ID.
+
+ALTER TABLE "Clients" ADD COLUMN "uuid" uuid NOT NULL DEFAULT (uuid_generate_v4());
+
+ALTER TABLE "Logs" ADD COLUMN "client_uuid" uuid;
+
+UPDATE "Logs"
+SET "client_uuid" = "Clients"."uuid"
+FROM "Clients"
+WHERE "Logs"."client_id" = "Clients"."id";
+
+ALTER TABLE "ApiKeys" ADD COLUMN "client_uuid" uuid;
+
+UPDATE "ApiKeys"
+SET "client_uuid" = "Clients"."uuid"
+FROM "Clients"
+WHERE "ApiKeys"."client_id" = "Clients"."id";
+
+-- dropping the old columns also drops the keys and indexes that include them.
+ALTER TABLE "Logs" DROP COLUMN "client_id";
+ALTER TABLE "Logs" RENAME COLUMN "client_uuid" TO "client_id";
+
+ALTER TABLE "ApiKeys" DROP COLUMN "client_id";
+ALTER TABLE "ApiKeys" RENAME COLUMN "client_uuid" TO "client_id";
+ALTER TABLE "ApiKeys" ALTER COLUMN "client_id" SET NOT NULL;
+
+ALTER TABLE "Clients" DROP COLUMN "id";
+ALTER TABLE "Clients" RENAME COLUMN "uuid" TO "id";
+ALTER TABLE "Clients" ADD PRIMARY KEY ("id");
+
+ALTER TABLE "Logs" ADD PRIMARY KEY ("id", "client_id");
+
+ALTER TABLE "Logs" ADD FOREIGN KEY ("client_id") REFERENCES "Clients" ("id");
+
+ALTER TABLE "ApiKeys" ADD FOREIGN KEY ("client_id") REFERENCES "Clients" ("id") ON DELETE CASCADE;
+
+CREATE INDEX ON "Logs" ("client_id", "date", "id");
+
+CREATE INDEX ON "ApiKeys" ("client_id");
@@ -0,0 +1,23 @@
+-- Projects group the clients that send logs and the clients that view them. Each log
+-- keeps the project its client belonged to when it was sent, so that moving a client to
+-- another project doesn't move the logs it already sent.
+
+CREATE TABLE "Projects" (
+  "id" uuid PRIMARY KEY DEFAULT (uuid_generate_v4()),
+  "name" text NOT NULL UNIQUE,
+  "description" text NOT NULL DEFAULT '',
+  "created_at" timestamp NOT NULL DEFAULT (now())
+);
+
+ALTER TABLE "Clients" ADD COLUMN "project_id" uuid;
+
+ALTER TABLE "Logs" ADD COLUMN "project_id" uuid;
+
+-- the clients and logs of a deleted project are kept, but no longer belong to a project.
+ALTER TABLE "Clients" ADD FOREIGN KEY ("project_id") REFERENCES "Projects" ("id") ON DELETE SET NULL;
+
+ALTER TABLE "Logs" ADD FOREIGN KEY ("project_id") REFERENCES "Projects" ("id") ON DELETE SET NULL;
+
+CREATE INDEX ON "Clients" ("project_id");
+
+CREATE INDEX ON "Logs" ("project_id", "date", "id");
@@ -0,0 +1,34 @@
+-- Local user accounts, the sessions they log in with, and the role each user has in
+-- the projects they belong to. Only a hash of each session token is stored.
+
+CREATE TABLE "Users" (
+  "id" uuid PRIMARY KEY DEFAULT (uuid_generate_v4()),
+  "username" text NOT NULL UNIQUE,
+  "password_hash" text NOT NULL,
+  "is_admin" boolean NOT NULL DEFAULT false,
+  "created_at" timestamp NOT NULL DEFAULT (now())
+);
+
+CREATE TABLE "Sessions" (
+  "token_hash" text PRIMARY KEY,
+  "user_id" uuid NOT NULL,
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "expires_at" timestamp NOT NULL
+);
+
+CREATE TABLE "ProjectMembers" (
+  "project_id" uuid NOT NULL,
+  "user_id" uuid NOT NULL,
+  "role" text NOT NULL CHECK ("role" IN ('admin', 'member', 'read_only')),
+  PRIMARY KEY ("project_id", "user_id")
+);
+
+ALTER TABLE "Sessions" ADD FOREIGN KEY ("user_id") REFERENCES "Users" ("id") ON DELETE CASCADE;
+
+ALTER TABLE "ProjectMembers" ADD FOREIGN KEY ("project_id") REFERENCES "Projects" ("id") ON DELETE CASCADE;
+
+ALTER TABLE "ProjectMembers" ADD FOREIGN KEY ("user_id") REFERENCES "Users" ("id") ON DELETE CASCADE;
+
+CREATE INDEX ON "Sessions" ("user_id");
+
+CREATE INDEX ON "ProjectMembers" ("user_id");
@@ -0,0 +1,15 @@
+-- Highlights the terms of `query` in `content`, HTML-escaping `content` first so that
+-- the `<mark>` tags added around the terms are the only markup in the result, and
+-- highlights can be rendered as HTML without trusting what producers sent.
+CREATE FUNCTION search_highlight("content" text, "query" tsquery, "options" text)
+RETURNS text
+LANGUAGE sql IMMUTABLE PARALLEL SAFE
+AS $$
+  SELECT ts_headline(
+    'english',
+    replace(replace(replace(replace(replace(
+      "content", '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '"', '&quot;'), '''', '&#39;'),
+    "query",
+    "options"
+  )
+$$;
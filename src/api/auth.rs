@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use argon2::{
+	password_hash::{rand_core::OsRng, SaltString},
+	Argon2,
+	PasswordHash,
+	PasswordHasher,
+	PasswordVerifier,
+};
+use axum::{
+	extract::State,
+	http::{header, HeaderMap, HeaderValue},
+	response::AppendHeaders,
+	Extension,
+	Json,
+};
+use chrono::Utc;
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::{
+	api::{
+		extractors::user::{fetch_user, session_token, User, SESSION_COOKIE},
+		Response,
+		Store,
+	},
+	prelude::*,
+	utils::{env::parse_var, token},
+};
+
+const DEFAULT_SESSION_TTL_SECONDS: u64 = 7 * 24 * 60 * 60;
+/// The number of random characters in a session token.
+const SESSION_TOKEN_LENGTH: usize = 48;
+
+/// How long sessions last, and whether their cookies are only sent over HTTPS, as set
+/// by the `TC_SESSION_TTL` (in seconds) and `TC_SECURE_COOKIES` environment variables.
+#[derive(Debug, Clone, Copy)]
+pub struct SessionConfig {
+	pub ttl: Duration,
+	pub secure: bool,
+}
+
+impl SessionConfig {
+	pub fn from_env() -> Self {
+		Self {
+			ttl: Duration::from_secs(
+				parse_var("TC_SESSION_TTL").unwrap_or(DEFAULT_SESSION_TTL_SECONDS),
+			),
+			secure: parse_var("TC_SECURE_COOKIES").unwrap_or(false),
+		}
+	}
+
+	/// The `Set-Cookie` header that stores `token` in the browser, or removes the cookie
+	/// if `token` is empty.
+	fn cookie(self, token: &str) -> Result<HeaderValue> {
+		let max_age = if token.is_empty() {
+			0
+		} else {
+			self.ttl.as_secs()
+		};
+		let secure = if self.secure { "; Secure" } else { "" };
+
+		HeaderValue::from_str(&fmt!(
+			"{SESSION_COOKIE}={token}; Max-Age={max_age}; Path=/; HttpOnly; SameSite=Lax{secure}"
+		))
+		.map_err(|err| Error::Generic(err.to_string()))
+	}
+}
+
+/// Hashes `password` with argon2, on a blocking thread since hashing is deliberately
+/// slow.
+pub async fn hash_password(password: String) -> Result<String> {
+	tokio::task::spawn_blocking(move || {
+		let salt = SaltString::generate(&mut OsRng);
+
+		Argon2::default()
+			.hash_password(password.as_bytes(), &salt)
+			.map(|hash| hash.to_string())
+			.map_err(|err| Error::Generic(err.to_string()))
+	})
+	.await
+	.map_err(|err| Error::Generic(err.to_string()))?
+}
+
+/// Whether `password` matches the argon2 `hash`.
+async fn verify_password(password: String, hash: String) -> Result<bool> {
+	tokio::task::spawn_blocking(move || {
+		let hash = PasswordHash::new(&hash).map_err(|err| Error::Generic(err.to_string()))?;
+
+		Ok(
+			Argon2::default()
+				.verify_password(password.as_bytes(), &hash)
+				.is_ok(),
+		)
+	})
+	.await
+	.map_err(|err| Error::Generic(err.to_string()))?
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct Credentials {
+	#[schema(example = "alice")]
+	pub username: String,
+	pub password: String,
+}
+
+#[utoipa::path(
+	post,
+	path="/api/auth/login",
+	request_body=Credentials,
+	responses(
+		(status=200, body=User, description="The user was logged in, and the session cookie was set"),
+		(status=401, description="The username or password was wrong"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn login(
+	Extension(pool): Extension<PgPool>,
+	State(store): State<Store>,
+	Json(credentials): Json<Credentials>,
+) -> Result<(
+	AppendHeaders<[(header::HeaderName, HeaderValue); 1]>,
+	Json<User>,
+)> {
+	let invalid = || {
+		Error::ResponseError(
+			StatusCode::UNAUTHORIZED,
+			"Username or password is wrong".into(),
+		)
+	};
+
+	let record = sqlx::query!(
+		r#"SELECT id, password_hash FROM "Users" WHERE username = $1"#,
+		credentials.username.trim()
+	)
+	.fetch_optional(&pool)
+	.await?
+	.ok_or_else(invalid)?;
+
+	if !verify_password(credentials.password, record.password_hash).await? {
+		return Err(invalid());
+	}
+
+	let session = token::generate(SESSION_TOKEN_LENGTH);
+	let expires_at = Utc::now() + store.sessions.ttl;
+
+	// expired sessions are cleaned up whenever someone logs in.
+	sqlx::query!(r#"DELETE FROM "Sessions" WHERE expires_at <= now()"#)
+		.execute(&pool)
+		.await?;
+
+	sqlx::query!(
+		r#"INSERT INTO "Sessions" (token_hash, user_id, expires_at) VALUES ($1, $2, $3)"#,
+		token::hash(&session),
+		record.id,
+		expires_at.naive_utc()
+	)
+	.execute(&pool)
+	.await?;
+
+	let user = fetch_user(&pool, record.id).await?.ok_or_else(invalid)?;
+	let cookie = store.sessions.cookie(&session)?;
+
+	Ok((AppendHeaders([(header::SET_COOKIE, cookie)]), Json(user)))
+}
+
+#[utoipa::path(
+	post,
+	path="/api/auth/logout",
+	responses(
+		(status=200, body=Response, description="The session was ended, and the session cookie was removed"),
+		(status=401, description="No one is logged in"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn logout(
+	_user: User,
+	Extension(pool): Extension<PgPool>,
+	State(store): State<Store>,
+	headers: HeaderMap,
+) -> Result<(
+	AppendHeaders<[(header::HeaderName, HeaderValue); 1]>,
+	Json<Response>,
+)> {
+	if let Some(session) = session_token(&headers) {
+		sqlx::query!(
+			r#"DELETE FROM "Sessions" WHERE token_hash = $1"#,
+			token::hash(session)
+		)
+		.execute(&pool)
+		.await?;
+	}
+
+	Ok((
+		AppendHeaders([(header::SET_COOKIE, store.sessions.cookie("")?)]),
+		Json(Response {
+			message: "Logged out".into(),
+			datetime: Utc::now(),
+		}),
+	))
+}
+
+#[utoipa::path(
+	get,
+	path="/api/auth/me",
+	responses(
+		(status=200, body=User, description="The user that is logged in, and the projects they belong to"),
+		(status=401, description="No one is logged in"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn me(user: User) -> Json<User> {
+	Json(user)
+}
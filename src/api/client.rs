@@ -1,12 +1,16 @@
 use axum::{extract::Path, Extension, Json};
 use sqlx::PgPool;
 use utoipa::ToSchema;
+use uuid::Uuid;
 
 use crate::prelude::*;
 
 #[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize, ToSchema)]
 pub struct RegisterClientResponse {
-	pub client_id: i32,
+	#[schema(example = "3f2c8a4e-5b1d-4c9e-8f7a-2d6b0e1c9a54")]
+	pub client_id: Uuid,
+	/// The project the client belongs to, if it has been added to one.
+	pub project_id: Option<Uuid>,
 }
 
 #[utoipa::path(
@@ -18,23 +22,24 @@ pub struct RegisterClientResponse {
 )]
 #[axum_macros::debug_handler]
 pub async fn new_client(pool: Extension<PgPool>) -> Result<Json<RegisterClientResponse>> {
-	register_client(pool, Path(0)).await
+	register_client(pool, Path(Uuid::nil())).await
 }
 
 #[utoipa::path(
 	post,
 	path="/api/get_or_register_client/{id}",
 	responses(
-		(status=200, body=RegisterClientResponse, description="The new or existing client ID. If the ID is new, it is highly unlikely that this id will be the same as the one that is returned."),
+		(status=200, body=RegisterClientResponse, description="The new or existing client ID. If the ID has not been registered, a new random ID is returned instead of it."),
+		(status=400, description="The `id` was not a valid UUID"),
 	),
 	params(
-		("id" = i32, Path, description = "New or existing client ID")
+		("id" = Uuid, Path, description = "New or existing client ID")
 	)
 )]
 #[axum_macros::debug_handler]
 pub async fn register_client(
 	Extension(pool): Extension<PgPool>,
-	Path(id): Path<i32>,
+	Path(id): Path<Uuid>,
 ) -> Result<Json<RegisterClientResponse>> {
 	if let Some(record) = sqlx::query!(r#"SELECT * FROM "Clients" WHERE id = $1"#, id)
 		.fetch_optional(&pool)
@@ -53,6 +58,7 @@ pub async fn register_client(
 
 		return Ok(Json(RegisterClientResponse {
 			client_id: record.id,
+			project_id: record.project_id,
 		}));
 	}
 
@@ -62,5 +68,6 @@ pub async fn register_client(
 
 	Ok(Json(RegisterClientResponse {
 		client_id: record.id,
+		project_id: None,
 	}))
 }
@@ -0,0 +1,21 @@
+use axum::{extract::State, Json};
+
+use crate::{
+	api::{extractors::user::ServerAdmin, Store},
+	utils::peer_map::PeerInfo,
+};
+
+#[utoipa::path(
+	get,
+	path="/api/connections",
+	responses(
+		(status=200, body=[PeerInfo], description="The websockets currently connected to the server, the longest connected first."),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn list_connections(
+	_admin: ServerAdmin,
+	State(store): State<Store>,
+) -> Json<Vec<PeerInfo>> {
+	Json(store.peers.list())
+}
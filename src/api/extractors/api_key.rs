@@ -0,0 +1,124 @@
+use axum::{
+	async_trait,
+	extract::FromRequestParts,
+	http::{header, request::Parts, StatusCode},
+	response::{IntoResponse, Response},
+	Extension,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{prelude::Error, utils::token};
+
+/// Every API key starts with this, to make them easy to recognise.
+const KEY_PREFIX: &str = "tc_";
+/// The number of random characters in a key, after [`KEY_PREFIX`].
+const KEY_LENGTH: usize = 40;
+/// The number of characters of a key that are stored as-is, so that it can be told
+/// apart from the other keys of its client.
+const SHOWN_LENGTH: usize = KEY_PREFIX.len() + 6;
+
+/// Generates a new API key, returning the key itself, the start of it that is safe to
+/// show, and the hash that is stored instead of the key.
+pub fn generate_key() -> (String, String, String) {
+	let key = format!("{KEY_PREFIX}{}", token::generate(KEY_LENGTH));
+	let shown = key[..SHOWN_LENGTH].to_owned();
+	let hash = token::hash(&key);
+
+	(key, shown, hash)
+}
+
+/// The client authenticated by the API key sent as a bearer token in the
+/// `Authorization` header, which must not have been revoked.
+#[derive(Debug, Clone, Copy)]
+pub struct ApiKey {
+	pub client_id: Uuid,
+}
+
+impl ApiKey {
+	/// Finds the client that `key` belongs to, returning [`None`] if the key doesn't
+	/// exist or has been revoked.
+	pub async fn authenticate(pool: &PgPool, key: &str) -> sqlx::Result<Option<Self>> {
+		let hash = token::hash(key.trim());
+
+		let Some(record) = sqlx::query!(
+			r###"
+			SELECT
+				id,
+				client_id,
+				last_used IS NULL OR last_used < now() - interval '1 minute' AS "stale!"
+			FROM "ApiKeys"
+			WHERE key_hash = $1 AND revoked_at IS NULL
+		"###,
+			hash
+		)
+		.fetch_optional(pool)
+		.await?
+		else {
+			return Ok(None);
+		};
+
+		// keys are used for every log sent, so when they were last used is only kept to
+		// the minute rather than written on every request.
+		if record.stale {
+			sqlx::query!(
+				r###"
+				UPDATE "ApiKeys"
+				SET last_used = now()
+				WHERE id = $1 AND (last_used IS NULL OR last_used < now() - interval '1 minute')
+			"###,
+				record.id
+			)
+			.execute(pool)
+			.await?;
+		}
+
+		Ok(Some(ApiKey {
+			client_id: record.client_id,
+		}))
+	}
+}
+
+fn unauthorized(message: &'static str) -> Response {
+	(
+		StatusCode::UNAUTHORIZED,
+		[(header::WWW_AUTHENTICATE, "Bearer")],
+		message,
+	)
+		.into_response()
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiKey
+where
+	S: Send + Sync,
+{
+	type Rejection = Response;
+
+	async fn from_request_parts(
+		parts: &mut Parts,
+		state: &S,
+	) -> Result<Self, Self::Rejection> {
+		let Extension(pool) = Extension::<PgPool>::from_request_parts(parts, state)
+			.await
+			.map_err(IntoResponse::into_response)?;
+
+		let key = parts
+			.headers
+			.get(header::AUTHORIZATION)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| value.strip_prefix("Bearer "))
+			.ok_or_else(|| unauthorized("`Authorization` header is missing or invalid"))?;
+
+		ApiKey::authenticate(&pool, key)
+			.await
+			.map_err(|err| {
+				// the caller hasn't been authenticated yet, so the details of the error are
+				// only logged.
+				tracing::error!("An error occurred: {}", Error::from(err));
+
+				(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response()
+			})?
+			.ok_or_else(|| unauthorized("API key is invalid or has been revoked"))
+	}
+}
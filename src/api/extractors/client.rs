@@ -7,9 +7,20 @@ use axum::{
 	Extension,
 };
 use sqlx::PgPool;
+use uuid::Uuid;
 
+/// The client sent in the `client-id` header, which must have been registered.
 #[derive(Debug, Clone, Copy)]
-pub struct ClientId(pub i32);
+pub struct ClientId(pub Uuid);
+
+/// Whether a client with the given `id` has been registered.
+pub async fn client_exists(pool: &PgPool, id: Uuid) -> sqlx::Result<bool> {
+	let client = sqlx::query!(r#"SELECT id FROM "Clients" WHERE id = $1"#, id)
+		.fetch_optional(pool)
+		.await?;
+
+	Ok(client.is_some())
+}
 
 #[async_trait]
 impl<S> FromRequestParts<S> for ClientId
@@ -30,14 +41,12 @@ where
 			let id = client_id
 				.to_str()
 				.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string().into()))?
-				.parse::<i32>()
+				.parse::<Uuid>()
 				.map_err(|err| (StatusCode::BAD_REQUEST, err.to_string().into()))?;
 
-			if sqlx::query!(r#"SELECT id FROM "Clients" WHERE id = $1"#, id)
-				.fetch_optional(&pool)
+			if client_exists(&pool, id)
 				.await
 				.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string().into()))?
-				.is_some()
 			{
 				Ok(ClientId(id))
 			} else {
@@ -1 +1,3 @@
+pub mod api_key;
 pub mod client;
+pub mod user;
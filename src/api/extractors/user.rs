@@ -0,0 +1,312 @@
+use std::{collections::HashMap, fmt::Display, marker::PhantomData, str::FromStr};
+
+use axum::{
+	async_trait,
+	extract::{FromRequestParts, Path},
+	http::{header, request::Parts, HeaderMap, StatusCode},
+	Extension,
+};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{prelude::*, utils::token};
+
+/// The cookie that holds the token of the session a user logged in with.
+pub const SESSION_COOKIE: &str = "tc_session";
+
+/// What a user can do in a project. Each role can do everything the roles before it
+/// can.
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	PartialEq,
+	Eq,
+	PartialOrd,
+	Ord,
+	serde::Deserialize,
+	serde::Serialize,
+	ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+	/// Can read the project and its logs.
+	ReadOnly,
+	/// Can also add clients to, and remove them from, the project.
+	Member,
+	/// Can also change or delete the project, and manage its members.
+	Admin,
+}
+
+impl Role {
+	/// The name of the role in the `"ProjectMembers"` table.
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Role::ReadOnly => "read_only",
+			Role::Member => "member",
+			Role::Admin => "admin",
+		}
+	}
+}
+
+impl Display for Role {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl FromStr for Role {
+	type Err = String;
+
+	fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+		match value {
+			"read_only" => Ok(Role::ReadOnly),
+			"member" => Ok(Role::Member),
+			"admin" => Ok(Role::Admin),
+			_ => Err(fmt!("'{value}' is not a valid role")),
+		}
+	}
+}
+
+/// The role a user has in a single project.
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct Membership {
+	pub project_id: Uuid,
+	pub role: Role,
+}
+
+/// The user logged in with the session cookie sent with the request.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct User {
+	pub id: Uuid,
+	#[schema(example = "alice")]
+	pub username: String,
+	/// Whether the user is an administrator of the whole server, who has the
+	/// [`Role::Admin`] role in every project.
+	pub is_admin: bool,
+	pub memberships: Vec<Membership>,
+}
+
+impl User {
+	/// The role of the user in the project with the given ID, if they have one.
+	pub fn role_in(&self, project_id: Uuid) -> Option<Role> {
+		if self.is_admin {
+			return Some(Role::Admin);
+		}
+
+		self
+			.memberships
+			.iter()
+			.find(|membership| membership.project_id == project_id)
+			.map(|membership| membership.role)
+	}
+
+	/// The projects whose logs the user can read, or [`None`] if they can read the logs
+	/// of every project, including logs that don't belong to a project.
+	pub fn readable_projects(&self) -> Option<Vec<Uuid>> {
+		self.projects_with(Role::ReadOnly)
+	}
+
+	/// The projects in which the user has at least the given `role`, or [`None`] if they
+	/// have it in every project, and over logs that don't belong to a project.
+	pub fn projects_with(&self, role: Role) -> Option<Vec<Uuid>> {
+		(!self.is_admin).then(|| {
+			self
+				.memberships
+				.iter()
+				.filter(|membership| membership.role >= role)
+				.map(|membership| membership.project_id)
+				.collect()
+		})
+	}
+}
+
+/// Returns the session token in the cookies of a request, if there is one.
+pub fn session_token(headers: &HeaderMap) -> Option<&str> {
+	headers
+		.get_all(header::COOKIE)
+		.iter()
+		.filter_map(|value| value.to_str().ok())
+		.flat_map(|cookies| cookies.split(';'))
+		.filter_map(|cookie| cookie.trim().split_once('='))
+		.find(|(name, _)| *name == SESSION_COOKIE)
+		.map(|(_, token)| token)
+}
+
+/// Loads the user with the given ID, along with their memberships.
+pub async fn fetch_user(pool: &PgPool, id: Uuid) -> Result<Option<User>> {
+	let Some(record) = sqlx::query!(
+		r#"SELECT id, username, is_admin FROM "Users" WHERE id = $1"#,
+		id
+	)
+	.fetch_optional(pool)
+	.await?
+	else {
+		return Ok(None);
+	};
+
+	let memberships = sqlx::query!(
+		r#"SELECT project_id, role FROM "ProjectMembers" WHERE user_id = $1"#,
+		id
+	)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.map(|membership| {
+		Ok(Membership {
+			project_id: membership.project_id,
+			role: membership
+				.role
+				.parse()
+				.map_err(|err| Error::ResponseError(StatusCode::INTERNAL_SERVER_ERROR, err))?,
+		})
+	})
+	.collect::<Result<_>>()?;
+
+	Ok(Some(User {
+		id: record.id,
+		username: record.username,
+		is_admin: record.is_admin,
+		memberships,
+	}))
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for User
+where
+	S: Send + Sync,
+{
+	type Rejection = Error;
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+		let Extension(pool) = Extension::<PgPool>::from_request_parts(parts, state)
+			.await
+			.map_err(|err| Error::ResponseError(err.status(), err.body_text()))?;
+
+		let unauthorized = || {
+			Error::ResponseError(
+				StatusCode::UNAUTHORIZED,
+				"You need to log in to do this".into(),
+			)
+		};
+
+		let token = session_token(&parts.headers).ok_or_else(unauthorized)?;
+
+		let user_id = sqlx::query_scalar!(
+			r#"SELECT user_id FROM "Sessions" WHERE token_hash = $1 AND expires_at > now()"#,
+			token::hash(token)
+		)
+		.fetch_optional(&pool)
+		.await?
+		.ok_or_else(unauthorized)?;
+
+		fetch_user(&pool, user_id).await?.ok_or_else(unauthorized)
+	}
+}
+
+/// A [`User`] who is an administrator of the whole server.
+#[derive(Debug, Clone)]
+pub struct ServerAdmin(pub User);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ServerAdmin
+where
+	S: Send + Sync,
+{
+	type Rejection = Error;
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+		let user = User::from_request_parts(parts, state).await?;
+
+		if !user.is_admin {
+			return Err(Error::ResponseError(
+				StatusCode::FORBIDDEN,
+				"Only server administrators can do this".into(),
+			));
+		}
+
+		Ok(ServerAdmin(user))
+	}
+}
+
+/// The roles that can be required by [`ProjectAccess`].
+pub mod roles {
+	use super::Role;
+
+	pub trait RequiredRole {
+		const ROLE: Role;
+	}
+
+	#[derive(Debug, Clone, Copy)]
+	pub struct ReadOnly;
+	#[derive(Debug, Clone, Copy)]
+	pub struct Member;
+	#[derive(Debug, Clone, Copy)]
+	pub struct Admin;
+
+	impl RequiredRole for ReadOnly {
+		const ROLE: Role = Role::ReadOnly;
+	}
+
+	impl RequiredRole for Member {
+		const ROLE: Role = Role::Member;
+	}
+
+	impl RequiredRole for Admin {
+		const ROLE: Role = Role::Admin;
+	}
+}
+
+/// Proof that the [`User`] logged in has at least the role `R` in the project whose ID
+/// is the `id` parameter in the path of the request.
+#[derive(Debug, Clone)]
+pub struct ProjectAccess<R> {
+	pub project_id: Uuid,
+	required: PhantomData<R>,
+}
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for ProjectAccess<R>
+where
+	S: Send + Sync,
+	R: roles::RequiredRole,
+{
+	type Rejection = Error;
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+		let user = User::from_request_parts(parts, state).await?;
+
+		let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+			.await
+			.map_err(|err| Error::ResponseError(err.status(), err.body_text()))?;
+		let project_id = params
+			.get("id")
+			.and_then(|id| id.parse::<Uuid>().ok())
+			.ok_or_else(|| {
+				Error::ResponseError(StatusCode::BAD_REQUEST, "Invalid project ID".into())
+			})?;
+
+		let Some(role) = user.role_in(project_id) else {
+			return Err(Error::ResponseError(
+				StatusCode::NOT_FOUND,
+				fmt!("Project with ID {project_id} not found"),
+			));
+		};
+
+		if role < R::ROLE {
+			return Err(Error::ResponseError(
+				StatusCode::FORBIDDEN,
+				fmt!(
+					"You need the {} role in project {project_id} to do this, but have the {role} role",
+					R::ROLE
+				),
+			));
+		}
+
+		Ok(ProjectAccess {
+			project_id,
+			required: PhantomData,
+		})
+	}
+}
@@ -0,0 +1,316 @@
+use axum::{extract::Path, Extension, Json};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+	api::{
+		extractors::{
+			api_key::{generate_key, ApiKey},
+			client::client_exists,
+			user::{Role, User},
+		},
+		projects::client_project,
+		Response,
+	},
+	prelude::*,
+};
+
+/// An API key, without the key itself.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct ApiKeyInfo {
+	pub id: Uuid,
+	#[schema(example = "3f2c8a4e-5b1d-4c9e-8f7a-2d6b0e1c9a54")]
+	pub client_id: Uuid,
+	/// The first few characters of the key.
+	#[schema(example = "tc_a1B2c3")]
+	pub prefix: String,
+	pub created_at: DateTime<Utc>,
+	/// When the key was last used, to the minute.
+	pub last_used: Option<DateTime<Utc>>,
+	/// When the key was revoked, or `null` if it can still be used.
+	pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A newly created API key. The key is only ever returned here, so it has to be kept
+/// by the client.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct NewApiKey {
+	pub id: Uuid,
+	#[schema(example = "3f2c8a4e-5b1d-4c9e-8f7a-2d6b0e1c9a54")]
+	pub client_id: Uuid,
+	/// The key, sent as a bearer token in the `Authorization` header.
+	pub key: String,
+	pub created_at: DateTime<Utc>,
+}
+
+/// Checks that the keys of the client with the given ID can be managed, either with
+/// one of its own keys, or by a user with at least the [`Role::Member`] role in the
+/// project of the client.
+async fn authorize(
+	pool: &PgPool,
+	api_key: Option<ApiKey>,
+	user: Option<User>,
+	client_id: Uuid,
+) -> Result<()> {
+	if api_key.is_some_and(|api_key| api_key.client_id == client_id) {
+		return Ok(());
+	}
+
+	if let Some(user) = user {
+		let role = if user.is_admin {
+			Some(Role::Admin)
+		} else {
+			client_project(pool, client_id)
+				.await?
+				.and_then(|project_id| user.role_in(project_id))
+		};
+
+		if role.is_some_and(|role| role >= Role::Member) {
+			return Ok(());
+		}
+
+		return Err(Error::ResponseError(
+			StatusCode::FORBIDDEN,
+			fmt!("You need the member role in the project of client {client_id} to do this"),
+		));
+	}
+
+	match api_key {
+		Some(_) => Err(Error::ResponseError(
+			StatusCode::FORBIDDEN,
+			fmt!("API key does not belong to client {client_id}"),
+		)),
+		None => Err(Error::ResponseError(
+			StatusCode::UNAUTHORIZED,
+			"An API key of the client, or a user session, is needed to do this".into(),
+		)),
+	}
+}
+
+async fn insert_key(
+	transaction: &mut Transaction<'_, Postgres>,
+	client_id: Uuid,
+) -> Result<NewApiKey> {
+	let (key, prefix, hash) = generate_key();
+
+	let record = sqlx::query!(
+		r###"
+			INSERT INTO "ApiKeys" (client_id, prefix, key_hash)
+			VALUES ($1, $2, $3)
+			RETURNING id, created_at
+		"###,
+		client_id,
+		prefix,
+		hash
+	)
+	.fetch_one(&mut **transaction)
+	.await?;
+
+	Ok(NewApiKey {
+		id: record.id,
+		client_id,
+		key,
+		created_at: record.created_at.and_utc(),
+	})
+}
+
+/// Revokes the key with the given `id` if it hasn't been revoked yet, returning its
+/// client. The key is only revoked once the transaction is committed, so it is left as
+/// it was if the caller turns out not to be allowed to revoke it.
+async fn revoke(transaction: &mut Transaction<'_, Postgres>, id: Uuid) -> Result<Uuid> {
+	let record = sqlx::query!(
+		r###"
+			UPDATE "ApiKeys"
+			SET revoked_at = now()
+			WHERE id = $1 AND revoked_at IS NULL
+			RETURNING client_id
+		"###,
+		id
+	)
+	.fetch_optional(&mut **transaction)
+	.await?;
+
+	record.map(|record| record.client_id).ok_or_else(|| {
+		Error::ResponseError(
+			StatusCode::NOT_FOUND,
+			fmt!("API key with ID {id} not found or already revoked"),
+		)
+	})
+}
+
+/// Creates an API key for a client. A client that isn't in a project and has never had
+/// a key can be given one by anyone, otherwise one of its keys that can still be used
+/// has to be sent, or a member of its project has to be logged in.
+#[utoipa::path(
+	post,
+	path="/api/clients/{id}/keys",
+	responses(
+		(status=200, body=NewApiKey, description="The new API key"),
+		(status=401, description="The client is in a project or has already had an API key, and no valid API key was sent and no one is logged in"),
+		(status=403, description="The API key sent belongs to another client, or the user logged in is not a member of the client's project"),
+		(status=404, description="The client with the given `id` was not found"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Client ID")
+	),
+	security((), ("api_key" = [])),
+)]
+#[axum_macros::debug_handler]
+pub async fn create_key(
+	api_key: Option<ApiKey>,
+	user: Option<User>,
+	Extension(pool): Extension<PgPool>,
+	Path(client_id): Path<Uuid>,
+) -> Result<Json<NewApiKey>> {
+	if !client_exists(&pool, client_id).await? {
+		return Err(Error::ResponseError(
+			StatusCode::NOT_FOUND,
+			fmt!("Client with ID {client_id} not found"),
+		));
+	}
+
+	let mut transaction = pool.begin().await?;
+
+	// the client's row is locked so that two first keys can't be created at once.
+	sqlx::query!(
+		r#"SELECT id FROM "Clients" WHERE id = $1 FOR UPDATE"#,
+		client_id
+	)
+	.fetch_one(&mut *transaction)
+	.await?;
+
+	let has_had_keys = sqlx::query_scalar!(
+		r#"SELECT EXISTS (SELECT 1 FROM "ApiKeys" WHERE client_id = $1) AS "exists!""#,
+		client_id
+	)
+	.fetch_one(&mut *transaction)
+	.await?;
+
+	// clients in a project are only given keys by the members of the project, and
+	// revoked keys count as well, so that revoking every key of a client locks it out.
+	if has_had_keys || client_project(&pool, client_id).await?.is_some() {
+		authorize(&pool, api_key, user, client_id).await?;
+	}
+
+	let new_key = insert_key(&mut transaction, client_id).await?;
+	transaction.commit().await?;
+
+	Ok(Json(new_key))
+}
+
+#[utoipa::path(
+	get,
+	path="/api/clients/{id}/keys",
+	responses(
+		(status=200, body=[ApiKeyInfo], description="Every API key of the client, including revoked keys, the newest first"),
+		(status=401, description="No valid API key was sent, and no one is logged in"),
+		(status=403, description="The API key sent belongs to another client, or the user logged in is not a member of the client's project"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Client ID")
+	),
+	security((), ("api_key" = [])),
+)]
+#[axum_macros::debug_handler]
+pub async fn list_keys(
+	api_key: Option<ApiKey>,
+	user: Option<User>,
+	Extension(pool): Extension<PgPool>,
+	Path(client_id): Path<Uuid>,
+) -> Result<Json<Vec<ApiKeyInfo>>> {
+	authorize(&pool, api_key, user, client_id).await?;
+
+	let keys = sqlx::query!(
+		r###"
+			SELECT id, client_id, prefix, created_at, last_used, revoked_at
+			FROM "ApiKeys"
+			WHERE client_id = $1
+			ORDER BY created_at DESC
+		"###,
+		client_id
+	)
+	.fetch_all(&pool)
+	.await?
+	.into_iter()
+	.map(|record| ApiKeyInfo {
+		id: record.id,
+		client_id: record.client_id,
+		prefix: record.prefix,
+		created_at: record.created_at.and_utc(),
+		last_used: record.last_used.map(|date| date.and_utc()),
+		revoked_at: record.revoked_at.map(|date| date.and_utc()),
+	})
+	.collect();
+
+	Ok(Json(keys))
+}
+
+/// Revokes an API key, and creates a new key for the same client in its place.
+#[utoipa::path(
+	post,
+	path="/api/keys/{id}/rotate",
+	responses(
+		(status=200, body=NewApiKey, description="The API key that replaces the revoked key"),
+		(status=401, description="No valid API key was sent, and no one is logged in"),
+		(status=403, description="The API key sent belongs to another client, or the user logged in is not a member of the client's project"),
+		(status=404, description="The API key with the given `id` was not found, or was already revoked"),
+	),
+	params(
+		("id" = Uuid, Path, description = "API key ID")
+	),
+	security((), ("api_key" = [])),
+)]
+#[axum_macros::debug_handler]
+pub async fn rotate_key(
+	api_key: Option<ApiKey>,
+	user: Option<User>,
+	Extension(pool): Extension<PgPool>,
+	Path(id): Path<Uuid>,
+) -> Result<Json<NewApiKey>> {
+	let mut transaction = pool.begin().await?;
+
+	let client_id = revoke(&mut transaction, id).await?;
+	authorize(&pool, api_key, user, client_id).await?;
+
+	let new_key = insert_key(&mut transaction, client_id).await?;
+	transaction.commit().await?;
+
+	Ok(Json(new_key))
+}
+
+#[utoipa::path(
+	delete,
+	path="/api/keys/{id}",
+	responses(
+		(status=200, body=Response, description="The API key was revoked, and can no longer be used"),
+		(status=401, description="No valid API key was sent, and no one is logged in"),
+		(status=403, description="The API key sent belongs to another client, or the user logged in is not a member of the client's project"),
+		(status=404, description="The API key with the given `id` was not found, or was already revoked"),
+	),
+	params(
+		("id" = Uuid, Path, description = "API key ID")
+	),
+	security((), ("api_key" = [])),
+)]
+#[axum_macros::debug_handler]
+pub async fn revoke_key(
+	api_key: Option<ApiKey>,
+	user: Option<User>,
+	Extension(pool): Extension<PgPool>,
+	Path(id): Path<Uuid>,
+) -> Result<Json<Response>> {
+	let mut transaction = pool.begin().await?;
+
+	let client_id = revoke(&mut transaction, id).await?;
+	authorize(&pool, api_key, user, client_id).await?;
+
+	transaction.commit().await?;
+
+	Ok(Json(Response {
+		message: fmt!("API key with ID {id} was revoked"),
+		datetime: Utc::now(),
+	}))
+}
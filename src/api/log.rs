@@ -1,31 +1,60 @@
 use std::{
-	collections::BTreeMap,
+	collections::{BTreeMap, HashMap, HashSet},
 	net::{IpAddr, SocketAddr},
 };
 
 use axum::{
-	extract::{ConnectInfo, Path, State},
+	body::Bytes,
+	extract::{ConnectInfo, Path, Query, State},
+	http::{header::CONTENT_TYPE, HeaderMap},
 	Extension,
 	Json,
 };
-use chrono::Utc;
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
 use common_macros::b_tree_map;
 use reqwest::StatusCode;
-use sqlx::{types::ipnetwork::IpNetwork, PgPool};
-use utoipa::ToSchema;
+use sqlx::{types::ipnetwork::IpNetwork, PgPool, Postgres, QueryBuilder, Transaction};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 use crate::{
 	api::{
-		extractors::client::ClientId,
-		types::{Layer, Log, Trace},
+		extractors::{
+			api_key::ApiKey,
+			client::ClientId,
+			user::{Role, User},
+		},
+		projects::client_project,
+		types::{
+			Cursor,
+			Layer,
+			Log,
+			LogFilter,
+			LogPage,
+			Page,
+			SortOrder,
+			Trace,
+			DEFAULT_PAGE_SIZE,
+			MAX_PAGE_SIZE,
+		},
 		Response,
 		Store,
 	},
 	prelude::*,
+	retention::collect_garbage,
+	utils::log_socket::{DeletedLog, LogEvent},
 };
 
-fn socket_addr_to_ip_network(socket_addr: &SocketAddr) -> IpNetwork {
+/// The maximum number of logs that can be sent in a single request to [`add_logs`].
+pub const MAX_BATCH_SIZE: usize = 10_000;
+/// How many times [`insert_logs`] tries to store layers that are being removed by the
+/// retention task at the same time.
+const MAX_LAYER_ATTEMPTS: u32 = 3;
+/// The most logs replayed to a live stream that fell behind or was paused. Anything
+/// past this has to be fetched from `GET /api/logs` instead.
+pub const MAX_REPLAY_SIZE: usize = 10 * MAX_PAGE_SIZE as usize;
+
+pub fn socket_addr_to_ip_network(socket_addr: &SocketAddr) -> IpNetwork {
 	let ip = socket_addr.ip();
 	IpNetwork::new(ip, single_host_prefix(&ip))
 		.expect("single_host_prefix created invalid prefix")
@@ -61,159 +90,605 @@ pub struct LogBody {
 	pub warnings: Vec<String>,
 }
 
-impl From<LogBody> for Log {
-	fn from(value: LogBody) -> Log {
-		let value = value.clone();
-
+impl LogBody {
+	/// Turns the body into a new [`Log`] sent by `client_id` in `project_id` from
+	/// `received_from`.
+	fn into_log(
+		self,
+		client_id: Uuid,
+		project_id: Option<Uuid>,
+		received_from: IpNetwork,
+	) -> Log {
 		Log {
 			id: Uuid::new_v4(),
-			message: value.message,
-			message_type: value.message_type,
-			language: value.language,
-			backtrace: value.backtrace,
-			snippet: value.snippet,
-			line_number: value.line_number,
-			warnings: value.warnings,
-			file_name: value.file_name,
-			date: Utc::now(),
-			received_from: None,
+			client_id,
+			project_id,
+			message: self.message,
+			message_type: self.message_type,
+			language: self.language,
+			backtrace: self.backtrace,
+			snippet: self.snippet,
+			line_number: self.line_number,
+			warnings: self.warnings,
+			file_name: self.file_name,
+			// the database only stores microseconds, so the date is truncated here to keep
+			// it the same as the stored log, for example when used in a cursor.
+			date: Utc::now().trunc_subsecs(6),
+			received_from: Some(received_from),
 		}
 	}
 }
 
-async fn list_logs_for_user(
-	pool: PgPool,
-	ClientId(client_id): ClientId,
-) -> Result<Json<Vec<Log>>> {
-	let mut logs = vec![];
+/// The outcome of a single item sent to [`add_logs`], in the same position as the item
+/// was in the request.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BatchItemResult {
+	/// The log was stored with the given ID.
+	Created { id: Uuid },
+	/// The item could not be parsed into a [`LogBody`], and was not stored.
+	Rejected { error: String },
+}
 
-	let log_records = sqlx::query!(
-		r###"
-	SELECT * FROM "Logs"
-	WHERE client_id = $1
-	"###,
-		client_id
+/// A single log, and the logs sent by the same client just before and after it, so
+/// that they can be paged through one at a time.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct LogDetail {
+	pub log: Log,
+	/// The log sent by the same client before this one, or `null` if this is its first.
+	pub previous: Option<Log>,
+	/// The log sent by the same client after this one, or `null` if this is its latest.
+	pub next: Option<Log>,
+}
+
+/// What was deleted by a request to delete logs.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct DeleteResponse {
+	pub message: String,
+	pub datetime: DateTime<Utc>,
+	/// The number of logs deleted.
+	pub logs: u64,
+	/// Backtraces deleted because the logs using them were deleted.
+	pub backtraces: u64,
+	/// Layers deleted because no remaining backtrace uses them.
+	pub layers: u64,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct BatchResponse {
+	pub message: String,
+	pub datetime: DateTime<Utc>,
+	pub results: Vec<BatchItemResult>,
+}
+
+/// Splits the body of a batch request into its individual items, accepting either a
+/// JSON array or newline-delimited JSON (when the `Content-Type` is
+/// `application/x-ndjson`). Items that can't be parsed are returned as errors, rather
+/// than failing the whole batch.
+fn parse_batch(
+	headers: &HeaderMap,
+	body: &[u8],
+) -> Result<Vec<std::result::Result<LogBody, String>>> {
+	let is_ndjson = headers
+		.get(CONTENT_TYPE)
+		.and_then(|content_type| content_type.to_str().ok())
+		.is_some_and(|content_type| {
+			content_type.starts_with("application/x-ndjson")
+				|| content_type.starts_with("application/ndjson")
+		});
+
+	if is_ndjson {
+		return Ok(
+			body
+				.split(|byte| *byte == b'\n')
+				.filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+				.map(|line| serde_json::from_slice(line).map_err(|err| err.to_string()))
+				.collect(),
+		);
+	}
+
+	let items: Vec<serde_json::Value> = serde_json::from_slice(body).map_err(|err| {
+		Error::ResponseError(
+			StatusCode::BAD_REQUEST,
+			fmt!("Expected a JSON array of logs: {err}"),
+		)
+	})?;
+
+	Ok(
+		items
+			.into_iter()
+			.map(|item| serde_json::from_value(item).map_err(|err| err.to_string()))
+			.collect(),
 	)
-	.fetch_all(&pool)
+}
+
+/// Writes `logs` sent by `client_id` to the database as part of `transaction`, using a
+/// fixed number of bulk inserts regardless of how many logs or layers there are. This
+/// covers the backtraces, layers, join rows, logs and the client's counters, so nothing
+/// written here is visible until the caller commits the transaction.
+async fn insert_logs(
+	transaction: &mut Transaction<'_, Postgres>,
+	client_id: Uuid,
+	received_from: IpNetwork,
+	logs: &[Log],
+) -> Result<()> {
+	if logs.is_empty() {
+		return Ok(());
+	}
+
+	let backtrace_ids = sqlx::query_scalar!(
+		r#"INSERT INTO "Backtraces" SELECT FROM generate_series(1, $1::int) RETURNING id"#,
+		logs.len() as i32
+	)
+	.fetch_all(&mut **transaction)
 	.await?;
 
-	for log_record in log_records {
-		let mut traces = vec![];
-		let trace_records = sqlx::query!(
+	let frames = logs
+		.iter()
+		.zip(&backtrace_ids)
+		.flat_map(|(log, backtrace_id)| {
+			log
+				.backtrace
+				.layers
+				.iter()
+				.enumerate()
+				.map(move |(position, layer)| (*backtrace_id, position as i32, layer))
+		})
+		.collect::<Vec<_>>();
+
+	let line_numbers = frames
+		.iter()
+		.map(|(_, _, layer)| layer.line_number)
+		.collect::<Vec<_>>();
+	let column_numbers = frames
+		.iter()
+		.map(|(_, _, layer)| layer.column_number)
+		.collect::<Vec<_>>();
+	let codes = frames
+		.iter()
+		.map(|(_, _, layer)| layer.code.clone())
+		.collect::<Vec<_>>();
+	let names = frames
+		.iter()
+		.map(|(_, _, layer)| layer.name.clone())
+		.collect::<Vec<_>>();
+	let file_paths = frames
+		.iter()
+		.map(|(_, _, layer)| layer.file_path.clone().unwrap_or_default())
+		.collect::<Vec<_>>();
+
+	// identical layers are only stored once, so any that already exist are left alone
+	// and looked up afterwards along with the ones that were just inserted. they're
+	// locked when looked up, so that they can't be removed by the retention task before
+	// this transaction has finished using them. an existing layer can still be removed
+	// between the insert and the lookup, in which case it is missing from the lookup and
+	// both are tried again, inserting it anew.
+	let mut attempts = 0;
+	let layer_ids = loop {
+		sqlx::query!(
 			r###"
-			SELECT "Layers".*
-			FROM "BacktracesLayers" 
-			JOIN "Backtraces" ON backtrace_id="Backtraces".id 
-			JOIN "Layers" 		ON layer_id="Layers".id 
-			WHERE backtrace_id = $1
+			INSERT INTO "Layers" (line_number, column_number, code, name, file_path)
+			SELECT DISTINCT * FROM UNNEST($1::int[], $2::int[], $3::text[], $4::text[], $5::text[])
+			ON CONFLICT (fingerprint) DO NOTHING
 			"###,
-			log_record.backtrace_id
+			&line_numbers,
+			&column_numbers,
+			&codes,
+			&names,
+			&file_paths,
 		)
-		.fetch_all(&pool)
+		.execute(&mut **transaction)
 		.await?;
 
-		for trace in trace_records {
-			traces.push(Layer {
-				line_number: trace.line_number,
-				column_number: trace.column_number,
-				code: trace.code,
-				name: trace.name,
-				file_path: Some(trace.file_path),
-			});
+		let layer_ids = sqlx::query_scalar!(
+			r###"
+			SELECT "Layers".id
+			FROM UNNEST($1::int[], $2::int[], $3::text[], $4::text[], $5::text[])
+				WITH ORDINALITY AS t(line_number, column_number, code, name, file_path, ordinal)
+			JOIN "Layers" ON "Layers".fingerprint = layer_fingerprint(
+				t.name, t.file_path, t.code, t.line_number, t.column_number
+			)
+			ORDER BY t.ordinal
+			FOR KEY SHARE OF "Layers"
+			"###,
+			&line_numbers,
+			&column_numbers,
+			&codes,
+			&names,
+			&file_paths,
+		)
+		.fetch_all(&mut **transaction)
+		.await?;
+
+		if layer_ids.len() == frames.len() {
+			break layer_ids;
+		}
+
+		attempts += 1;
+
+		if attempts == MAX_LAYER_ATTEMPTS {
+			return Err(Error::Generic(fmt!(
+				"Could not store the layers of {} logs after {MAX_LAYER_ATTEMPTS} attempts, \
+				 as they kept being removed by the retention task",
+				logs.len()
+			)));
 		}
 
-		let log = Log {
-			id: log_record.id,
-			message: log_record.message.clone(),
-			language: log_record.language.clone(),
-			snippet: serde_json::from_value(log_record.snippet.clone())?,
-			backtrace: Trace { layers: traces },
-			warnings: log_record.warnings.clone(),
-			date: log_record.date.and_utc(),
-			received_from: log_record.received_from,
-			message_type: log_record.message_type,
-			line_number: log_record.line_number,
-			file_name: log_record.file_name,
-		};
-
-		logs.push(log);
+		tracing::debug!("Layers were removed while being stored, trying again");
+	};
+
+	sqlx::query!(
+		r###"
+		INSERT INTO "BacktracesLayers" (backtrace_id, position, layer_id)
+		SELECT * FROM UNNEST($1::int[], $2::int[], $3::int[])
+		"###,
+		&frames
+			.iter()
+			.map(|(backtrace_id, ..)| *backtrace_id)
+			.collect::<Vec<_>>(),
+		&frames
+			.iter()
+			.map(|(_, position, _)| *position)
+			.collect::<Vec<_>>(),
+		&layer_ids,
+	)
+	.execute(&mut **transaction)
+	.await?;
+
+	let mut snippets = Vec::with_capacity(logs.len());
+	let mut warnings = Vec::with_capacity(logs.len());
+	for log in logs {
+		snippets.push(serde_json::to_value(&log.snippet)?);
+		warnings.push(serde_json::to_value(&log.warnings)?);
 	}
 
-	Ok(Json(logs))
+	sqlx::query!(
+		r###"
+		INSERT INTO "Logs" (
+			id,
+			client_id,
+			project_id,
+			message,
+			message_type,
+			language,
+			snippet,
+			line_number,
+			backtrace_id,
+			warnings,
+			date,
+			file_name,
+			received_from
+		)
+		SELECT
+			id, $1::uuid, project_id, message,
+			message_type, language, snippet::json,
+			line_number, backtrace_id, ARRAY(SELECT jsonb_array_elements_text(warnings)),
+			date, file_name, $2::inet
+		FROM UNNEST(
+			$3::uuid[], $4::text[], $5::text[],
+			$6::text[], $7::jsonb[], $8::int[],
+			$9::int[], $10::jsonb[], $11::timestamp[],
+			$12::text[], $13::uuid[]
+		) AS t(
+			id, message, message_type,
+			language, snippet, line_number,
+			backtrace_id, warnings, date,
+			file_name, project_id
+		)
+		"###,
+		client_id,
+		received_from,
+		&logs.iter().map(|log| log.id).collect::<Vec<_>>(),
+		&logs
+			.iter()
+			.map(|log| log.message.clone())
+			.collect::<Vec<_>>(),
+		&logs
+			.iter()
+			.map(|log| log.message_type.clone())
+			.collect::<Vec<_>>(),
+		&logs
+			.iter()
+			.map(|log| log.language.clone())
+			.collect::<Vec<_>>(),
+		&snippets,
+		&logs.iter().map(|log| log.line_number).collect::<Vec<_>>(),
+		&backtrace_ids,
+		&warnings,
+		&logs
+			.iter()
+			.map(|log| log.date.naive_utc())
+			.collect::<Vec<_>>(),
+		&logs
+			.iter()
+			.map(|log| log.file_name.clone())
+			.collect::<Vec<_>>(),
+		&logs.iter().map(|log| log.project_id).collect::<Vec<_>>() as &[Option<Uuid>],
+	)
+	.execute(&mut **transaction)
+	.await?;
+
+	sqlx::query!(
+		r###"
+			UPDATE "Clients"
+			SET last_connected = now(), logs_sent = logs_sent + $2
+			WHERE id = $1
+		"###,
+		client_id,
+		logs.len() as i32
+	)
+	.execute(&mut **transaction)
+	.await?;
+
+	Ok(())
 }
 
-async fn list_server_logs(pool: PgPool) -> Result<Json<Vec<Log>>> {
-	let mut log_records = sqlx::query!(r#"SELECT * FROM "Logs""#)
-		.fetch_all(&pool)
-		.await?;
+/// Hands `logs` to the recent logs cache and any websocket subscribers. This must only be
+/// called once the logs have been committed to the database, so that nothing is sent to
+/// the front-end that wasn't actually persisted.
+fn publish_logs(store: &Store, logs: Vec<Log>) {
+	store.recent_logs.extend(logs.iter().cloned());
 
-	let mut logs = vec![];
+	for log in logs {
+		if let Err(err) = store.sender.send(LogEvent::Created(Box::new(log))) {
+			tracing::error!("Could not send log to back-end: {err}");
+			return;
+		}
+	}
 
-	for log in &mut log_records {
-		let mut traces = vec![];
-		let trace_records = sqlx::query!(
-			r###"
-			SELECT "Layers".*
-			FROM "BacktracesLayers" 
-			JOIN "Backtraces" ON backtrace_id="Backtraces".id 
-			JOIN "Layers" 		ON layer_id="Layers".id 
-			WHERE backtrace_id = $1
-			"###,
-			log.backtrace_id
-		)
-		.fetch_all(&pool)
-		.await?;
+	tracing::info!("Sent log to backend");
+}
+
+/// Stores `bodies` as logs sent by `client_id` from `received_from`, and broadcasts them
+/// to everyone watching, returning the logs in the same order.
+pub async fn create_logs(
+	pool: &PgPool,
+	store: &Store,
+	client_id: Uuid,
+	received_from: IpNetwork,
+	bodies: Vec<LogBody>,
+) -> Result<Vec<Log>> {
+	let project_id = client_project(pool, client_id).await?;
+	let logs = bodies
+		.into_iter()
+		.map(|body| body.into_log(client_id, project_id, received_from))
+		.collect::<Vec<_>>();
+
+	let mut transaction = pool.begin().await?;
+	insert_logs(&mut transaction, client_id, received_from, &logs).await?;
+	transaction.commit().await?;
+
+	publish_logs(store, logs.clone());
+
+	Ok(logs)
+}
+
+/// The columns of the `"Logs"` table that a [`LogRecord`] is read from, which are
+/// listed rather than selecting every column so that those only used by the database,
+/// such as the `search` index, aren't fetched with every log.
+pub const LOG_COLUMNS: &str = r#""Logs".id, "Logs".client_id, "Logs".project_id, "Logs".message,
+	"Logs".message_type, "Logs".file_name, "Logs".language, "Logs".snippet,
+	"Logs".line_number, "Logs".backtrace_id, "Logs".warnings, "Logs".date,
+	"Logs".received_from"#;
+
+/// A row from the `"Logs"` table, before its backtrace has been loaded.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct LogRecord {
+	pub id: Uuid,
+	pub client_id: Uuid,
+	pub project_id: Option<Uuid>,
+	pub message: String,
+	pub message_type: String,
+	pub file_name: String,
+	pub language: String,
+	pub snippet: serde_json::Value,
+	pub line_number: i32,
+	pub backtrace_id: i32,
+	pub warnings: Vec<String>,
+	pub date: NaiveDateTime,
+	pub received_from: Option<IpNetwork>,
+}
+
+/// Loads the layers of every backtrace in `backtrace_ids` using a single query, with
+/// each backtrace's layers in the order they were originally sent.
+async fn fetch_traces(
+	pool: &PgPool,
+	backtrace_ids: &[i32],
+) -> Result<HashMap<i32, Trace>> {
+	let records = sqlx::query!(
+		r###"
+		SELECT
+			"BacktracesLayers".backtrace_id,
+			"Layers".line_number,
+			"Layers".column_number,
+			"Layers".code,
+			"Layers".name,
+			"Layers".file_path
+		FROM "BacktracesLayers"
+		JOIN "Layers" ON layer_id = "Layers".id
+		WHERE backtrace_id = ANY($1)
+		ORDER BY backtrace_id, position
+		"###,
+		backtrace_ids
+	)
+	.fetch_all(pool)
+	.await?;
+
+	let mut traces: HashMap<i32, Trace> = HashMap::new();
 
-		for trace in trace_records {
-			traces.push(Layer {
-				line_number: trace.line_number,
-				column_number: trace.column_number,
-				code: trace.code,
-				name: trace.name,
-				file_path: Some(trace.file_path),
+	for record in records {
+		traces
+			.entry(record.backtrace_id)
+			.or_default()
+			.layers
+			.push(Layer {
+				line_number: record.line_number,
+				column_number: record.column_number,
+				code: record.code,
+				name: record.name,
+				file_path: Some(record.file_path),
 			});
-		}
+	}
+
+	Ok(traces)
+}
+
+/// Turns `records` into [`Log`]s, loading all of their backtraces at once.
+pub async fn into_logs(pool: &PgPool, records: Vec<LogRecord>) -> Result<Vec<Log>> {
+	let backtrace_ids = records
+		.iter()
+		.map(|record| record.backtrace_id)
+		.collect::<Vec<_>>();
+	let mut traces = fetch_traces(pool, &backtrace_ids).await?;
+
+	records
+		.into_iter()
+		.map(|record| {
+			Ok(Log {
+				id: record.id,
+				client_id: record.client_id,
+				project_id: record.project_id,
+				message: record.message,
+				message_type: record.message_type,
+				language: record.language,
+				snippet: serde_json::from_value(record.snippet)?,
+				line_number: record.line_number,
+				backtrace: traces.remove(&record.backtrace_id).unwrap_or_default(),
+				warnings: record.warnings,
+				date: record.date.and_utc(),
+				received_from: record.received_from,
+				file_name: record.file_name,
+			})
+		})
+		.collect()
+}
 
-		let log = Log {
-			id: log.id,
-			message: log.message.clone(),
-			language: log.language.clone(),
-			snippet: serde_json::from_value(log.snippet.clone())?,
-			backtrace: Trace { layers: traces },
-			warnings: log.warnings.clone(),
-			date: log.date.and_utc(),
-			received_from: log.received_from,
-			message_type: log.message_type.clone(),
-			line_number: log.line_number,
-			file_name: log.file_name.clone(),
-		};
-
-		logs.push(log);
+/// Fetches a single page of the logs matching `filter`, only including those sent by
+/// one of `client_ids` unless it is empty.
+pub async fn fetch_logs(
+	pool: &PgPool,
+	client_ids: &[Uuid],
+	filter: &LogFilter,
+	page: &Page,
+) -> Result<LogPage> {
+	let mut builder =
+		QueryBuilder::new(fmt!(r#"SELECT {LOG_COLUMNS} FROM "Logs" WHERE TRUE"#));
+
+	if !client_ids.is_empty() {
+		builder
+			.push(r#" AND "Logs".client_id = ANY("#)
+			.push_bind(client_ids.to_vec())
+			.push(")");
 	}
 
-	Ok(Json(logs))
+	filter.push_conditions(&mut builder);
+	page.push_ordering(&mut builder);
+
+	let mut records = builder
+		.build_query_as::<LogRecord>()
+		.fetch_all(pool)
+		.await?;
+
+	let has_next_page = records.len() as i64 > page.limit();
+	records.truncate(page.limit() as usize);
+
+	let logs = into_logs(pool, records).await?;
+	let next_cursor = logs
+		.last()
+		.filter(|_| has_next_page)
+		.map(|log| Cursor::from(log).to_string());
+
+	Ok(LogPage { logs, next_cursor })
+}
+
+/// Fetches the next page of logs matching `filter` that come after `cursor`, oldest
+/// first, only including those sent by one of `client_ids` unless it is empty. This is
+/// used to catch up on the logs missed by a live stream, a page at a time, until
+/// [`MAX_REPLAY_SIZE`] logs have been replayed.
+pub async fn fetch_logs_after(
+	pool: &PgPool,
+	client_ids: &[Uuid],
+	filter: &LogFilter,
+	cursor: Cursor,
+) -> Result<LogPage> {
+	let page = Page {
+		limit: Some(MAX_PAGE_SIZE),
+		cursor: Some(cursor),
+		order: Some(SortOrder::Asc),
+	};
+
+	fetch_logs(pool, client_ids, filter, &page).await
 }
 
 #[utoipa::path(
 	get,
 	path="/api/logs",
 	responses(
-		(status=200, description="List all the logs received by the server, or logs sent to the given `client-id` if present.", body=[Log])
+		(status=200, description="A page of the logs that match the given filters in the projects the user belongs to, or only the logs sent to the given `client-id` if present.", body=LogPage),
+		(status=400, description="One of the query parameters was invalid"),
+		(status=401, description="No one is logged in"),
 	),
 	params(
-		("client-id" = Option<i32>, Header, description = "Client ID (optional)"),
+		("client-id" = Option<Uuid>, Header, description = "Client ID (optional)"),
+		LogFilter,
+		Page,
 	),
 )]
 #[axum_macros::debug_handler]
 pub async fn list_logs(
+	user: User,
 	client_id: Option<ClientId>,
 	Extension(pool): Extension<PgPool>,
-) -> Result<Json<Vec<Log>>> {
-	if let Some(client_id) = client_id {
-		list_logs_for_user(pool, client_id).await
-	} else {
-		list_server_logs(pool).await
-	}
+	Query(mut filter): Query<LogFilter>,
+	Query(page): Query<Page>,
+) -> Result<Json<LogPage>> {
+	let client_id = client_id.map(|ClientId(client_id)| client_id);
+	filter.project_ids = user.readable_projects();
+
+	fetch_logs(&pool, client_id.as_slice(), &filter, &page)
+		.await
+		.map(Json)
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RecentLogsQuery {
+	/// The maximum number of logs to return, defaults to 100.
+	#[param(minimum = 1)]
+	pub limit: Option<usize>,
+}
+
+#[utoipa::path(
+	get,
+	path="/api/logs/recent",
+	responses(
+		(status=200, description="The most recent logs received by the server since it started in the projects the user belongs to, newest first, or only the logs sent to the given `client-id` if present. These are served from memory, so are limited by the size of the cache rather than `limit`.", body=[Log]),
+		(status=401, description="No one is logged in"),
+	),
+	params(
+		("client-id" = Option<Uuid>, Header, description = "Client ID (optional)"),
+		RecentLogsQuery,
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn list_recent_logs(
+	user: User,
+	client_id: Option<ClientId>,
+	State(store): State<Store>,
+	Query(query): Query<RecentLogsQuery>,
+) -> Json<Vec<Log>> {
+	let client_id = client_id.map(|ClientId(client_id)| client_id);
+	let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE as usize);
+	let filter = LogFilter {
+		project_ids: user.readable_projects(),
+		..LogFilter::default()
+	};
+
+	Json(
+		store
+			.recent_logs
+			.latest_matching(client_id.as_slice(), limit, |log| filter.matches(log)),
+	)
 }
 
 #[utoipa::path(
@@ -222,203 +697,371 @@ pub async fn list_logs(
 	request_body=LogBody,
 	responses(
 		(status=200, description="Log was created"),
+		(status=401, description="No valid API key was sent"),
 		(status=500, description="An internal server error occurred")
 	),
-	params(
-		("client-id" = i32, Header, description = "Client ID"),
-	),
+	security(("api_key" = [])),
 )]
 #[axum_macros::debug_handler]
 pub async fn add_log(
-	ClientId(client_id): ClientId,
+	ApiKey { client_id }: ApiKey,
 	Extension(pool): Extension<PgPool>,
 	State(store): State<Store>,
 	ConnectInfo(addr): ConnectInfo<SocketAddr>,
 	Json(log): Json<LogBody>,
 ) -> Result<Json<Response>> {
-	let mut log: Log = log.into();
 	let ip_network = socket_addr_to_ip_network(&addr);
+	let logs = create_logs(&pool, &store, client_id, ip_network, vec![log]).await?;
 
-	log.received_from = Some(ip_network);
-
-	{
-		let mut logs = store.logs.lock();
+	Ok(Json(Response {
+		message: fmt!("Log was created with ID {}", logs[0].id),
+		datetime: logs[0].date,
+	}))
+}
 
-		logs.push(log.clone());
-	}
+#[utoipa::path(
+	post,
+	path="/api/logs/batch",
+	request_body(
+		content=[LogBody],
+		description="An array of logs, or newline-delimited JSON with one log per line when sent as `application/x-ndjson`",
+	),
+	responses(
+		(status=200, body=BatchResponse, description="The logs that could be parsed were created, with a result for each item in the order they were sent"),
+		(status=400, description="The body was not a JSON array or newline-delimited JSON"),
+		(status=401, description="No valid API key was sent"),
+		(status=413, description="The batch contained too many logs"),
+		(status=500, description="An internal server error occurred, and none of the logs were created")
+	),
+	security(("api_key" = [])),
+)]
+#[axum_macros::debug_handler]
+pub async fn add_logs(
+	ApiKey { client_id }: ApiKey,
+	Extension(pool): Extension<PgPool>,
+	State(store): State<Store>,
+	ConnectInfo(addr): ConnectInfo<SocketAddr>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> Result<Json<BatchResponse>> {
+	let items = parse_batch(&headers, &body)?;
 
-	if let Err(err) = store.sender.send(log.clone()) {
-		tracing::error!("Could not send log to back-end: {err}");
-	} else {
-		tracing::info!("Sent log to backend");
+	if items.len() > MAX_BATCH_SIZE {
+		return Err(Error::ResponseError(
+			StatusCode::PAYLOAD_TOO_LARGE,
+			fmt!(
+				"Batch contained {} logs, the maximum is {MAX_BATCH_SIZE}",
+				items.len()
+			),
+		));
 	}
 
-	let backtrace_id =
-		sqlx::query!(r#"INSERT INTO "Backtraces" DEFAULT VALUES RETURNING id"#)
-			.fetch_one(&pool)
-			.await?
-			.id;
-
-	let layer_results = log.backtrace.layers.iter().map(|layer| {
-		sqlx::query!(
-			r###"
-				INSERT INTO "Layers" (line_number, column_number, code, name, file_path) 
-				VALUES ($1, $2, $3, $4, $5) 
-				RETURNING id
-				"###,
-			layer.line_number,
-			layer.column_number,
-			layer.code.clone(),
-			layer.name.clone(),
-			layer.file_path.clone(),
-		)
-		.fetch_one(&pool)
-	});
+	let ip_network = socket_addr_to_ip_network(&addr);
+	let project_id = client_project(&pool, client_id).await?;
+	let mut logs = Vec::with_capacity(items.len());
+	let mut results = Vec::with_capacity(items.len());
 
-	for future in layer_results {
-		let layer = future.await?;
+	for item in items {
+		match item {
+			Ok(body) => {
+				let log = body.into_log(client_id, project_id, ip_network);
 
-		sqlx::query!(
-			r###"
-			INSERT INTO "BacktracesLayers" (backtrace_id, layer_id) 
-			VALUES ($1, $2)
-			"###,
-			backtrace_id,
-			layer.id,
-		)
-		.execute(&pool)
-		.await?;
+				results.push(BatchItemResult::Created { id: log.id });
+				logs.push(log);
+			}
+			Err(error) => results.push(BatchItemResult::Rejected { error }),
+		}
 	}
 
-	let log_id = sqlx::query!(
-		r###"
-		INSERT INTO "Logs" (
-			client_id,
-			message,
-			message_type,
-			language,
-			snippet,
-			line_number,
-			backtrace_id,
-			warnings,
-			date,
-			file_name,
-			received_from
-		) 
-		VALUES (
-			$1, $2, $3,
-			$4, $5, $6,
-			$7, $8, $9,
-			$10, $11
-		) 
-		RETURNING id
-		"###,
-		client_id,
-		log.message.clone(),
-		log.message_type.clone(),
-		log.language.clone(),
-		serde_json::to_value(&log.snippet)?,
-		log.line_number,
-		backtrace_id,
-		&log.warnings.clone(),
-		log.date.naive_utc(),
-		log.file_name.clone(),
-		ip_network,
-	)
-	.fetch_one(&pool)
-	.await?
-	.id;
+	let mut transaction = pool.begin().await?;
+	insert_logs(&mut transaction, client_id, ip_network, &logs).await?;
+	transaction.commit().await?;
 
-	sqlx::query!(
-		r###"
-			UPDATE "Clients"
-			SET last_connected = now(), logs_sent = logs_sent + 1
-			WHERE id = $1
-		"###,
-		client_id
-	)
-	.execute(&pool)
-	.await?;
+	let created = logs.len();
+	publish_logs(&store, logs);
 
-	Ok(Json(Response {
-		message: fmt!("Log was created with ID {log_id}"),
-		datetime: log.date,
+	Ok(Json(BatchResponse {
+		message: fmt!("Created {created} of {} logs", results.len()),
+		datetime: Utc::now(),
+		results,
 	}))
 }
 
+/// Fetches the log sent by the same client as `log` that comes just after it in the
+/// given `order`, which is the next log in a page of the client's logs matching `filter`.
+async fn fetch_neighbour(
+	pool: &PgPool,
+	log: &Log,
+	filter: &LogFilter,
+	order: SortOrder,
+) -> Result<Option<Log>> {
+	let page = Page {
+		limit: Some(1),
+		cursor: Some(Cursor::from(log)),
+		order: Some(order),
+	};
+
+	let LogPage { logs, .. } = fetch_logs(pool, &[log.client_id], filter, &page).await?;
+
+	Ok(logs.into_iter().next())
+}
+
 #[utoipa::path(
 	get,
 	path="/api/log/{id}",
 	responses(
-		(status=200, body=Log, description="The log with the given `id`"),
-		(status=404, description="The log with the given `id` was not found"),
+		(status=200, body=LogDetail, description="The log with the given `id`, along with the logs sent by the same client just before and after it"),
+		(status=401, description="No one is logged in"),
+		(status=404, description="The log with the given `id` was not found in the projects the user belongs to, or was not sent by the given `client-id`"),
 	),
 	params(
-		("client-id" = i32, Header, description = "Client ID"),
+		("client-id" = Option<Uuid>, Header, description = "Client ID (optional)"),
 		("id" = Uuid, Path, description = "Log ID")
 	),
 )]
 #[axum_macros::debug_handler]
 pub async fn get_log(
-	ClientId(client_id): ClientId,
+	user: User,
+	client_id: Option<ClientId>,
 	Extension(pool): Extension<PgPool>,
 	Path(id): Path<Uuid>,
-) -> Result<Json<Log>> {
-	let log_record = sqlx::query!(
+) -> Result<Json<LogDetail>> {
+	let filter = LogFilter {
+		project_ids: user.readable_projects(),
+		..LogFilter::default()
+	};
+
+	let log_record = sqlx::query_as!(
+		LogRecord,
 		r###"
-			SELECT * FROM "Logs" 
-			WHERE id = $1 AND backtrace_id = $2
-	"###,
+			SELECT
+				id, client_id, project_id, message, message_type,
+				file_name, language, snippet,
+				line_number, backtrace_id, warnings,
+				date, received_from
+			FROM "Logs"
+			WHERE id = $1
+				AND ($2::uuid IS NULL OR client_id = $2)
+				AND ($3::uuid[] IS NULL OR project_id = ANY($3))
+		"###,
 		id,
-		client_id
+		client_id.map(|ClientId(client_id)| client_id),
+		filter.project_ids.as_deref()
 	)
 	.fetch_optional(&pool)
 	.await?;
 
-	if log_record.is_none() {
+	let Some(log_record) = log_record else {
 		return Err(Error::ResponseError(
 			StatusCode::NOT_FOUND,
-			format!("Log with ID {id} not found for client ID {client_id}"),
+			fmt!("Log with ID {id} not found"),
 		));
+	};
+
+	let log = into_logs(&pool, vec![log_record]).await?.remove(0);
+
+	let previous = fetch_neighbour(&pool, &log, &filter, SortOrder::Desc).await?;
+	let next = fetch_neighbour(&pool, &log, &filter, SortOrder::Asc).await?;
+
+	Ok(Json(LogDetail {
+		log,
+		previous,
+		next,
+	}))
+}
+
+/// Deletes the logs matching `filter`, only including those sent by `client_id` if
+/// present, or only the log with the given `id` if present. The logs are deleted in
+/// batches of the retention policy's size, each of which is sent to every live
+/// connection once it is deleted, after which the backtraces and layers that are no
+/// longer used are deleted.
+async fn delete_matching(
+	pool: &PgPool,
+	store: &Store,
+	id: Option<Uuid>,
+	client_id: Option<Uuid>,
+	filter: &LogFilter,
+) -> Result<DeleteResponse> {
+	let batch_size = store.retention_policy.batch_size;
+	let mut logs = 0;
+
+	loop {
+		let mut builder = QueryBuilder::new(
+			r#"DELETE FROM "Logs" WHERE id IN (SELECT "Logs".id FROM "Logs" WHERE TRUE"#,
+		);
+
+		if let Some(id) = id {
+			builder.push(r#" AND "Logs".id = "#).push_bind(id);
+		}
+
+		if let Some(client_id) = client_id {
+			builder
+				.push(r#" AND "Logs".client_id = "#)
+				.push_bind(client_id);
+		}
+
+		filter.push_conditions(&mut builder);
+		builder
+			.push(" LIMIT ")
+			.push_bind(batch_size)
+			.push(") RETURNING id, client_id, project_id");
+
+		let batch = builder
+			.build_query_as::<(Uuid, Uuid, Option<Uuid>)>()
+			.fetch_all(pool)
+			.await?;
+		let batch_len = batch.len();
+
+		if batch_len > 0 {
+			let deleted = batch
+				.into_iter()
+				.map(|(id, client_id, project_id)| DeletedLog {
+					id,
+					client_id,
+					project_id,
+				})
+				.collect::<Vec<_>>();
+
+			let ids = deleted.iter().map(|log| log.id).collect::<HashSet<_>>();
+			store.recent_logs.retain(|log| !ids.contains(&log.id));
+			logs += batch_len as u64;
+
+			// there may not be anyone connected to tell.
+			let _ = store.sender.send(LogEvent::Deleted(deleted));
+		}
+
+		if (batch_len as i64) < batch_size {
+			break;
+		}
+	}
+
+	if logs == 0 {
+		return Ok(DeleteResponse {
+			message: "No logs were deleted".into(),
+			datetime: Utc::now(),
+			logs: 0,
+			backtraces: 0,
+			layers: 0,
+		});
 	}
 
-	let log_record = log_record.unwrap();
+	let (backtraces, layers) = collect_garbage(pool, batch_size).await?;
+
+	Ok(DeleteResponse {
+		message: fmt!("Deleted {logs} logs"),
+		datetime: Utc::now(),
+		logs,
+		backtraces,
+		layers,
+	})
+}
+
+/// Deletes a single log, along with its backtrace and layers if no other log uses them.
+#[utoipa::path(
+	delete,
+	path="/api/log/{id}",
+	responses(
+		(status=200, body=DeleteResponse, description="The log was deleted"),
+		(status=401, description="No one is logged in"),
+		(status=403, description="The user logged in needs the member role in the project of the log to delete it"),
+		(status=404, description="The log with the given `id` was not found in the projects the user belongs to"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Log ID")
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn delete_log(
+	user: User,
+	Extension(pool): Extension<PgPool>,
+	State(store): State<Store>,
+	Path(id): Path<Uuid>,
+) -> Result<Json<DeleteResponse>> {
+	let filter = LogFilter {
+		project_ids: user.projects_with(Role::Member),
+		..LogFilter::default()
+	};
+
+	let response = delete_matching(&pool, &store, Some(id), None, &filter).await?;
 
-	let mut layers = vec![];
-	let trace_records = sqlx::query!(
+	if response.logs > 0 {
+		return Ok(Json(response));
+	}
+
+	let readable_projects = user.readable_projects();
+	let readable = sqlx::query_scalar!(
 		r###"
-			SELECT "Layers".*
-			FROM "BacktracesLayers" 
-			JOIN "Backtraces" ON backtrace_id="Backtraces".id 
-			JOIN "Layers" 		ON layer_id="Layers".id 
-			WHERE backtrace_id = $1
-			"###,
-		log_record.backtrace_id
+			SELECT EXISTS (
+				SELECT 1 FROM "Logs"
+				WHERE id = $1 AND ($2::uuid[] IS NULL OR project_id = ANY($2))
+			) AS "exists!"
+		"###,
+		id,
+		readable_projects.as_deref()
 	)
-	.fetch_all(&pool)
+	.fetch_one(&pool)
 	.await?;
 
-	for trace in trace_records {
-		layers.push(Layer {
-			line_number: trace.line_number,
-			column_number: trace.column_number,
-			code: trace.code,
-			name: trace.name,
-			file_path: Some(trace.file_path),
-		});
+	if readable {
+		Err(Error::ResponseError(
+			StatusCode::FORBIDDEN,
+			fmt!("You need the member role in the project of log {id} to delete it"),
+		))
+	} else {
+		Err(Error::ResponseError(
+			StatusCode::NOT_FOUND,
+			fmt!("Log with ID {id} not found"),
+		))
 	}
+}
 
-	Ok(Json(Log {
-		id: log_record.id,
-		message: log_record.message.clone(),
-		message_type: log_record.message_type.clone(),
-		language: log_record.language.clone(),
-		snippet: serde_json::from_value(log_record.snippet.clone())?,
-		line_number: log_record.line_number,
-		backtrace: Trace { layers },
-		warnings: log_record.warnings.clone(),
-		date: log_record.date.and_utc(),
-		received_from: log_record.received_from,
-		file_name: log_record.file_name,
-	}))
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteLogsQuery {
+	/// Must be `true` to delete logs without any filters, which deletes every log in the
+	/// projects where the user has the member role.
+	#[serde(default)]
+	pub all: bool,
+}
+
+/// Deletes every log matching the given filters in the projects where the user has at
+/// least the member role, along with the backtraces and layers no longer used by any
+/// log. At least one filter, or `all=true`, has to be given.
+#[utoipa::path(
+	delete,
+	path="/api/logs",
+	responses(
+		(status=200, body=DeleteResponse, description="The matching logs were deleted, if there were any"),
+		(status=400, description="One of the query parameters was invalid, or no filters were given without `all=true`"),
+		(status=401, description="No one is logged in"),
+	),
+	params(
+		("client-id" = Option<Uuid>, Header, description = "Client ID (optional)"),
+		DeleteLogsQuery,
+		LogFilter,
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn delete_logs(
+	user: User,
+	client_id: Option<ClientId>,
+	Extension(pool): Extension<PgPool>,
+	State(store): State<Store>,
+	Query(query): Query<DeleteLogsQuery>,
+	Query(mut filter): Query<LogFilter>,
+) -> Result<Json<DeleteResponse>> {
+	let client_id = client_id.map(|ClientId(client_id)| client_id);
+
+	if client_id.is_none() && filter.is_empty() && !query.all {
+		return Err(Error::ResponseError(
+			StatusCode::BAD_REQUEST,
+			"No filters were given, pass `all=true` to delete every log".into(),
+		));
+	}
+
+	filter.project_ids = user.projects_with(Role::Member);
+
+	delete_matching(&pool, &store, None, client_id, &filter)
+		.await
+		.map(Json)
 }
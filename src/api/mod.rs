@@ -1,16 +1,27 @@
+pub mod auth;
 mod client;
-mod extractors;
-mod log;
+mod connections;
+pub mod extractors;
+mod keys;
+pub mod log;
+mod projects;
+mod retention;
+mod search;
+mod stream;
 pub mod types;
+mod users;
 
 use std::{net::SocketAddr, sync::Arc};
 
 use axum::{
 	body::Body,
-	extract::ConnectInfo,
-	http::Request,
+	extract::{ConnectInfo, DefaultBodyLimit},
+	http::{
+		header::{AUTHORIZATION, COOKIE},
+		Request,
+	},
 	response::IntoResponse,
-	routing::{get, post},
+	routing::{delete, get, post, put},
 	Extension,
 	Router,
 };
@@ -19,6 +30,7 @@ use parking_lot::Mutex;
 use reqwest::StatusCode;
 use sqlx::PgPool;
 use tower_http::{
+	sensitive_headers::SetSensitiveRequestHeadersLayer,
 	trace::{
 		DefaultMakeSpan,
 		DefaultOnFailure,
@@ -29,49 +41,187 @@ use tower_http::{
 	LatencyUnit,
 };
 use tracing::Level;
-use utoipa::{OpenApi, ToSchema};
+use utoipa::{
+	openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+	Modify,
+	OpenApi,
+	ToSchema,
+};
 
 use crate::{
-	api::types::Log,
-	utils::{log_socket::LogSender, uuid::Uuid},
+	api::auth::SessionConfig,
+	retention::{PruneReport, RetentionPolicy},
+	utils::{
+		log_socket::LogSender,
+		peer_map::PeerMap,
+		recent_logs::RecentLogs,
+		uuid::Uuid,
+	},
+	ws::{self, Heartbeat},
 };
 
-#[derive(OpenApi)]
-#[openapi(
-	info(
-		title = "TraceCTRL",
-		description = "API documentation for the TraceCTRL REST server",
-	),
-	paths(
-		log::list_logs,
-		log::add_log,
-		log::get_log,
-		client::new_client,
-		client::register_client
-	),
-	components(schemas(
-		Uuid,
+// the derive expands to a `for_each` over the registered paths, which can only be
+// allowed on the module around it.
+#[allow(clippy::needless_for_each)]
+mod docs {
+	use super::{
+		auth,
+		client,
+		connections,
+		extractors,
+		keys,
+		log,
+		projects,
+		retention,
+		search,
+		stream,
+		types,
+		users,
+		OpenApi,
 		Response,
-		types::Log,
-		types::Trace,
-		types::Layer,
-		log::LogBody,
-		client::RegisterClientResponse,
-	))
-)]
-pub struct ApiDoc;
+		SecurityAddon,
+		Uuid,
+	};
+
+	#[derive(OpenApi)]
+	#[openapi(
+		info(
+			title = "TraceCTRL",
+			description = "API documentation for the TraceCTRL REST server",
+		),
+		paths(
+			log::list_logs,
+			log::add_log,
+			log::add_logs,
+			log::get_log,
+			log::delete_log,
+			log::delete_logs,
+			log::list_recent_logs,
+			search::search_logs,
+			stream::stream_logs,
+			retention::get_retention,
+			connections::list_connections,
+			client::new_client,
+			client::register_client,
+			keys::create_key,
+			keys::list_keys,
+			keys::rotate_key,
+			keys::revoke_key,
+			projects::list_projects,
+			projects::create_project,
+			projects::get_project,
+			projects::update_project,
+			projects::delete_project,
+			projects::add_client,
+			projects::remove_client,
+			projects::list_project_logs,
+			projects::list_members,
+			projects::set_member,
+			projects::remove_member,
+			auth::login,
+			auth::logout,
+			auth::me,
+			users::create_user,
+			users::list_users,
+			users::delete_user
+		),
+		components(schemas(
+			Uuid,
+			Response,
+			types::Log,
+			types::Trace,
+			types::Layer,
+			types::LogPage,
+			types::SortOrder,
+			types::LogFilter,
+			log::LogBody,
+			log::LogDetail,
+			log::DeleteResponse,
+			log::BatchItemResult,
+			log::BatchResponse,
+			client::RegisterClientResponse,
+			keys::ApiKeyInfo,
+			keys::NewApiKey,
+			projects::Project,
+			projects::ProjectBody,
+			projects::ProjectUpdate,
+			projects::ProjectMember,
+			projects::MemberBody,
+			auth::Credentials,
+			users::UserInfo,
+			users::NewUser,
+			extractors::user::User,
+			extractors::user::Membership,
+			extractors::user::Role,
+			search::SearchResults,
+			search::SearchResult,
+			search::Highlights,
+			search::LayerHighlight,
+			retention::RetentionStatus,
+			crate::retention::RetentionPolicy,
+			crate::retention::PruneReport,
+			crate::utils::peer_map::PeerInfo,
+			crate::ws::protocol::Subscription,
+			crate::ws::protocol::ClientEnvelope,
+			crate::ws::protocol::ClientMessage,
+			crate::ws::protocol::ServerEnvelope,
+			crate::ws::protocol::ServerMessage,
+		)),
+		modifiers(&SecurityAddon)
+	)]
+	pub struct ApiDoc;
+}
+
+pub use docs::ApiDoc;
+
+/// Adds the API keys that clients send their logs with to the documentation.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+	fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+		let components = openapi.components.get_or_insert_with(Default::default);
+
+		components.add_security_scheme(
+			"api_key",
+			SecurityScheme::Http(
+				HttpBuilder::new()
+					.scheme(HttpAuthScheme::Bearer)
+					.description(Some(
+						"An API key created with `POST /api/clients/{id}/keys`",
+					))
+					.build(),
+			),
+		);
+	}
+}
 
 #[derive(Debug, Clone)]
 pub struct Store {
-	pub logs: Arc<Mutex<Vec<Log>>>,
+	pub recent_logs: RecentLogs,
 	pub sender: LogSender,
+	pub peers: PeerMap,
+	pub retention_policy: RetentionPolicy,
+	pub heartbeat: Heartbeat,
+	pub sessions: SessionConfig,
+	pub last_prune: Arc<Mutex<Option<PruneReport>>>,
 }
 
 impl Store {
-	pub fn new(sender: LogSender) -> Self {
+	pub fn new(
+		sender: LogSender,
+		recent_logs: RecentLogs,
+		retention_policy: RetentionPolicy,
+		heartbeat: Heartbeat,
+		sessions: SessionConfig,
+	) -> Self {
 		Self {
-			logs: Arc::default(),
+			recent_logs,
 			sender,
+			peers: PeerMap::new(),
+			retention_policy,
+			heartbeat,
+			sessions,
+			last_prune: Arc::default(),
 		}
 	}
 }
@@ -84,6 +234,9 @@ pub struct Response {
 
 pub struct ApiRouter;
 
+/// The maximum size of the body accepted by [`log::add_logs`], in bytes.
+const BATCH_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
 #[axum_macros::debug_handler]
 async fn fallback(
 	ConnectInfo(addr): ConnectInfo<SocketAddr>,
@@ -95,15 +248,61 @@ async fn fallback(
 }
 
 impl ApiRouter {
-	pub fn new_router(log_sender: LogSender, pool: PgPool) -> Router {
-		Router::new()
-			.route("/logs", get(log::list_logs))
+	/// Creates the router for the REST API under `/api`, and the websocket that
+	/// front-ends receive logs through at `/ws`.
+	pub fn new_router(store: Store, pool: PgPool) -> Router {
+		let api = Router::new()
+			.route("/logs", get(log::list_logs).delete(log::delete_logs))
+			.route("/logs/search", get(search::search_logs))
+			.route("/logs/recent", get(log::list_recent_logs))
+			.route("/logs/stream", get(stream::stream_logs))
 			.route("/log", post(log::add_log))
-			.route("/log/:id", get(log::get_log))
+			.route(
+				"/logs/batch",
+				post(log::add_logs).layer(DefaultBodyLimit::max(BATCH_BODY_LIMIT)),
+			)
+			.route("/log/:id", get(log::get_log).delete(log::delete_log))
 			.route("/get_or_register_client", post(client::new_client))
 			.route("/get_or_register_client/:id", post(client::register_client))
-			.with_state(Store::new(log_sender))
-			.fallback(fallback)
+			.route(
+				"/clients/:id/keys",
+				get(keys::list_keys).post(keys::create_key),
+			)
+			.route("/keys/:id/rotate", post(keys::rotate_key))
+			.route("/keys/:id", delete(keys::revoke_key))
+			.route(
+				"/projects",
+				get(projects::list_projects).post(projects::create_project),
+			)
+			.route(
+				"/projects/:id",
+				get(projects::get_project)
+					.patch(projects::update_project)
+					.delete(projects::delete_project),
+			)
+			.route(
+				"/projects/:id/clients/:client_id",
+				put(projects::add_client).delete(projects::remove_client),
+			)
+			.route("/projects/:id/logs", get(projects::list_project_logs))
+			.route("/projects/:id/members", get(projects::list_members))
+			.route(
+				"/projects/:id/members/:user_id",
+				put(projects::set_member).delete(projects::remove_member),
+			)
+			.route("/auth/login", post(auth::login))
+			.route("/auth/logout", post(auth::logout))
+			.route("/auth/me", get(auth::me))
+			.route("/users", get(users::list_users).post(users::create_user))
+			.route("/users/:id", delete(users::delete_user))
+			.route("/retention", get(retention::get_retention))
+			.route("/connections", get(connections::list_connections))
+			.fallback(fallback);
+
+		Router::new()
+			.nest("/api", api)
+			.route("/ws", get(ws::websocket))
+			.with_state(store)
 			.layer(Extension(pool))
 			.layer(
 				TraceLayer::new_for_http()
@@ -116,5 +315,11 @@ impl ApiRouter {
 							.latency_unit(LatencyUnit::Micros),
 					),
 			)
+			// API keys and session cookies are left out of the request headers that are
+			// logged.
+			.layer(SetSensitiveRequestHeadersLayer::new([
+				AUTHORIZATION,
+				COOKIE,
+			]))
 	}
 }
@@ -0,0 +1,636 @@
+use axum::{
+	extract::{Path, Query},
+	Extension,
+	Json,
+};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+	api::{
+		extractors::{
+			client::client_exists,
+			user::{roles, ProjectAccess, Role, User},
+		},
+		log::fetch_logs,
+		types::{LogFilter, LogPage, Page},
+		Response,
+	},
+	prelude::*,
+};
+
+/// A group of clients, both those that send logs and those that view them, whose logs
+/// are shared between them.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct Project {
+	pub id: Uuid,
+	#[schema(example = "Backend services")]
+	pub name: String,
+	pub description: String,
+	pub created_at: DateTime<Utc>,
+	/// The clients that belong to the project, the most recently connected first.
+	pub clients: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct ProjectBody {
+	/// The name of the project, which must be unique.
+	#[schema(example = "Backend services")]
+	pub name: String,
+	#[serde(default)]
+	pub description: String,
+}
+
+/// Changes to a project, where fields that are missing are left as they are.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct ProjectUpdate {
+	#[schema(example = "Backend services")]
+	pub name: Option<String>,
+	pub description: Option<String>,
+}
+
+fn not_found(id: Uuid) -> Error {
+	Error::ResponseError(
+		StatusCode::NOT_FOUND,
+		fmt!("Project with ID {id} not found"),
+	)
+}
+
+/// Checks that a project name isn't blank, returning it without surrounding whitespace.
+fn validate_name(name: &str) -> Result<String> {
+	let name = name.trim();
+
+	if name.is_empty() {
+		return Err(Error::ResponseError(
+			StatusCode::BAD_REQUEST,
+			"Project name cannot be empty".into(),
+		));
+	}
+
+	Ok(name.to_owned())
+}
+
+/// Turns the error of a query that sets the name of a project into a `409 Conflict` if
+/// another project already has that name.
+fn name_conflict(name: &str) -> impl FnOnce(sqlx::Error) -> Error + '_ {
+	move |err| match err {
+		sqlx::Error::Database(err) if err.is_unique_violation() => Error::ResponseError(
+			StatusCode::CONFLICT,
+			fmt!("A project named '{name}' already exists"),
+		),
+		err => err.into(),
+	}
+}
+
+/// Locks the project with the given ID until `transaction` ends, so that changes to its
+/// members are made one at a time.
+async fn lock_members(
+	transaction: &mut Transaction<'_, Postgres>,
+	id: Uuid,
+) -> Result<()> {
+	sqlx::query!(r#"SELECT id FROM "Projects" WHERE id = $1 FOR UPDATE"#, id)
+		.fetch_optional(&mut **transaction)
+		.await?
+		.ok_or_else(|| not_found(id))?;
+
+	Ok(())
+}
+
+/// Checks that the project with the given ID still has an admin once its members have
+/// been changed in `transaction`, so that it is never left without anyone who can
+/// manage it.
+async fn ensure_admin(
+	transaction: &mut Transaction<'_, Postgres>,
+	id: Uuid,
+) -> Result<()> {
+	let has_admin = sqlx::query_scalar!(
+		r#"
+		SELECT EXISTS (
+			SELECT 1 FROM "ProjectMembers" WHERE project_id = $1 AND role = $2
+		) AS "exists!"
+		"#,
+		id,
+		Role::Admin.as_str()
+	)
+	.fetch_one(&mut **transaction)
+	.await?;
+
+	if !has_admin {
+		return Err(Error::ResponseError(
+			StatusCode::CONFLICT,
+			"A project must have at least one admin".into(),
+		));
+	}
+
+	Ok(())
+}
+
+/// Returns the project that the client with the given ID belongs to, if any.
+pub async fn client_project(pool: &PgPool, client_id: Uuid) -> Result<Option<Uuid>> {
+	let project_id = sqlx::query_scalar!(
+		r#"SELECT project_id FROM "Clients" WHERE id = $1"#,
+		client_id
+	)
+	.fetch_optional(pool)
+	.await?
+	.flatten();
+
+	Ok(project_id)
+}
+
+/// Fetches every project sorted by name, or only the project with the given `id`.
+async fn fetch_projects(pool: &PgPool, id: Option<Uuid>) -> Result<Vec<Project>> {
+	let projects = sqlx::query!(
+		r###"
+			SELECT
+				"Projects".id, name, description, created_at,
+				coalesce(
+					array_agg("Clients".id ORDER BY "Clients".last_connected DESC)
+						FILTER (WHERE "Clients".id IS NOT NULL),
+					'{}'
+				) AS "clients!"
+			FROM "Projects"
+			LEFT JOIN "Clients" ON "Clients".project_id = "Projects".id
+			WHERE $1::uuid IS NULL OR "Projects".id = $1
+			GROUP BY "Projects".id
+			ORDER BY name
+		"###,
+		id
+	)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.map(|record| Project {
+		id: record.id,
+		name: record.name,
+		description: record.description,
+		created_at: record.created_at.and_utc(),
+		clients: record.clients,
+	})
+	.collect();
+
+	Ok(projects)
+}
+
+async fn fetch_project(pool: &PgPool, id: Uuid) -> Result<Project> {
+	fetch_projects(pool, Some(id))
+		.await?
+		.pop()
+		.ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+	get,
+	path="/api/projects",
+	responses(
+		(status=200, body=[Project], description="Every project the user belongs to, sorted by name"),
+		(status=401, description="No one is logged in"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn list_projects(
+	user: User,
+	Extension(pool): Extension<PgPool>,
+) -> Result<Json<Vec<Project>>> {
+	let mut projects = fetch_projects(&pool, None).await?;
+	projects.retain(|project| user.role_in(project.id).is_some());
+
+	Ok(Json(projects))
+}
+
+/// Creates a project, with the user that created it as its first admin.
+#[utoipa::path(
+	post,
+	path="/api/projects",
+	request_body=ProjectBody,
+	responses(
+		(status=200, body=Project, description="The new project"),
+		(status=400, description="The name of the project was empty"),
+		(status=401, description="No one is logged in"),
+		(status=409, description="A project with the same name already exists"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn create_project(
+	user: User,
+	Extension(pool): Extension<PgPool>,
+	Json(body): Json<ProjectBody>,
+) -> Result<Json<Project>> {
+	let name = validate_name(&body.name)?;
+
+	let mut transaction = pool.begin().await?;
+
+	let id = sqlx::query_scalar!(
+		r#"INSERT INTO "Projects" (name, description) VALUES ($1, $2) RETURNING id"#,
+		name,
+		body.description
+	)
+	.fetch_one(&mut *transaction)
+	.await
+	.map_err(name_conflict(&name))?;
+
+	sqlx::query!(
+		r#"INSERT INTO "ProjectMembers" (project_id, user_id, role) VALUES ($1, $2, $3)"#,
+		id,
+		user.id,
+		Role::Admin.as_str()
+	)
+	.execute(&mut *transaction)
+	.await?;
+
+	transaction.commit().await?;
+
+	fetch_project(&pool, id).await.map(Json)
+}
+
+#[utoipa::path(
+	get,
+	path="/api/projects/{id}",
+	responses(
+		(status=200, body=Project, description="The project with the given `id`"),
+		(status=401, description="No one is logged in"),
+		(status=404, description="The project with the given `id` was not found, or the user does not belong to it"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Project ID")
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn get_project(
+	access: ProjectAccess<roles::ReadOnly>,
+	Extension(pool): Extension<PgPool>,
+) -> Result<Json<Project>> {
+	fetch_project(&pool, access.project_id).await.map(Json)
+}
+
+#[utoipa::path(
+	patch,
+	path="/api/projects/{id}",
+	request_body=ProjectUpdate,
+	responses(
+		(status=200, body=Project, description="The updated project"),
+		(status=400, description="The new name of the project was empty"),
+		(status=403, description="The user is not an admin of the project"),
+		(status=401, description="No one is logged in"),
+		(status=404, description="The project with the given `id` was not found, or the user does not belong to it"),
+		(status=409, description="Another project already has the new name"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Project ID")
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn update_project(
+	ProjectAccess { project_id: id, .. }: ProjectAccess<roles::Admin>,
+	Extension(pool): Extension<PgPool>,
+	Json(update): Json<ProjectUpdate>,
+) -> Result<Json<Project>> {
+	let name = update.name.as_deref().map(validate_name).transpose()?;
+
+	let updated = sqlx::query!(
+		r###"
+			UPDATE "Projects"
+			SET name = coalesce($2, name), description = coalesce($3, description)
+			WHERE id = $1
+		"###,
+		id,
+		name,
+		update.description
+	)
+	.execute(&pool)
+	.await
+	.map_err(name_conflict(name.as_deref().unwrap_or_default()))?;
+
+	if updated.rows_affected() == 0 {
+		return Err(not_found(id));
+	}
+
+	fetch_project(&pool, id).await.map(Json)
+}
+
+#[utoipa::path(
+	delete,
+	path="/api/projects/{id}",
+	responses(
+		(status=200, body=Response, description="The project was deleted. Its clients and logs are kept, but no longer belong to a project."),
+		(status=403, description="The user is not an admin of the project"),
+		(status=401, description="No one is logged in"),
+		(status=404, description="The project with the given `id` was not found, or the user does not belong to it"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Project ID")
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn delete_project(
+	ProjectAccess { project_id: id, .. }: ProjectAccess<roles::Admin>,
+	Extension(pool): Extension<PgPool>,
+) -> Result<Json<Response>> {
+	let deleted = sqlx::query!(r#"DELETE FROM "Projects" WHERE id = $1"#, id)
+		.execute(&pool)
+		.await?;
+
+	if deleted.rows_affected() == 0 {
+		return Err(not_found(id));
+	}
+
+	Ok(Json(Response {
+		message: fmt!("Project with ID {id} was deleted"),
+		datetime: Utc::now(),
+	}))
+}
+
+#[utoipa::path(
+	put,
+	path="/api/projects/{id}/clients/{client_id}",
+	responses(
+		(status=200, body=Project, description="The client was added to the project, leaving any project it belonged to before. Logs it already sent stay in the project they were sent to."),
+		(status=401, description="No one is logged in"),
+		(status=403, description="The user is only a read-only member of the project, or is not a member of the project the client belongs to now"),
+		(status=404, description="The project or client was not found"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Project ID"),
+		("client_id" = Uuid, Path, description = "Client ID"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn add_client(
+	_access: ProjectAccess<roles::Member>,
+	user: User,
+	Extension(pool): Extension<PgPool>,
+	Path((id, client_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Project>> {
+	if !client_exists(&pool, client_id).await? {
+		return Err(Error::ResponseError(
+			StatusCode::NOT_FOUND,
+			fmt!("Client with ID {client_id} not found"),
+		));
+	}
+
+	// moving a client takes it, and the logs it sends from then on, away from its
+	// current project, so the user has to be a member there as well.
+	if let Some(current) = client_project(&pool, client_id).await? {
+		if user.role_in(current).is_none_or(|role| role < Role::Member) {
+			return Err(Error::ResponseError(
+				StatusCode::FORBIDDEN,
+				fmt!(
+					"You need the member role in project {current}, which client {client_id} \
+					 belongs to, to move it"
+				),
+			));
+		}
+	}
+
+	let updated = sqlx::query!(
+		r###"
+			UPDATE "Clients"
+			SET project_id = "Projects".id
+			FROM "Projects"
+			WHERE "Clients".id = $2 AND "Projects".id = $1
+		"###,
+		id,
+		client_id
+	)
+	.execute(&pool)
+	.await?;
+
+	if updated.rows_affected() == 0 {
+		return Err(not_found(id));
+	}
+
+	fetch_project(&pool, id).await.map(Json)
+}
+
+#[utoipa::path(
+	delete,
+	path="/api/projects/{id}/clients/{client_id}",
+	responses(
+		(status=200, body=Project, description="The client was removed from the project"),
+		(status=401, description="No one is logged in"),
+		(status=403, description="The user is only a read-only member of the project"),
+		(status=404, description="The client does not belong to the project"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Project ID"),
+		("client_id" = Uuid, Path, description = "Client ID"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn remove_client(
+	_access: ProjectAccess<roles::Member>,
+	Extension(pool): Extension<PgPool>,
+	Path((id, client_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Project>> {
+	let updated = sqlx::query!(
+		r#"UPDATE "Clients" SET project_id = NULL WHERE id = $2 AND project_id = $1"#,
+		id,
+		client_id
+	)
+	.execute(&pool)
+	.await?;
+
+	if updated.rows_affected() == 0 {
+		return Err(Error::ResponseError(
+			StatusCode::NOT_FOUND,
+			fmt!("Client with ID {client_id} does not belong to project {id}"),
+		));
+	}
+
+	fetch_project(&pool, id).await.map(Json)
+}
+
+#[utoipa::path(
+	get,
+	path="/api/projects/{id}/logs",
+	responses(
+		(status=200, body=LogPage, description="A page of the logs sent to the project that match the given filters"),
+		(status=400, description="One of the query parameters was invalid"),
+		(status=401, description="No one is logged in"),
+		(status=404, description="The project with the given `id` was not found, or the user does not belong to it"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Project ID"),
+		LogFilter,
+		Page,
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn list_project_logs(
+	ProjectAccess { project_id: id, .. }: ProjectAccess<roles::ReadOnly>,
+	Extension(pool): Extension<PgPool>,
+	Query(filter): Query<LogFilter>,
+	Query(page): Query<Page>,
+) -> Result<Json<LogPage>> {
+	fetch_project(&pool, id).await?;
+
+	let filter = LogFilter {
+		project_id: Some(id),
+		..filter
+	};
+
+	fetch_logs(&pool, &[], &filter, &page).await.map(Json)
+}
+
+/// A user that belongs to a project.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct ProjectMember {
+	pub user_id: Uuid,
+	#[schema(example = "alice")]
+	pub username: String,
+	pub role: Role,
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct MemberBody {
+	pub role: Role,
+}
+
+async fn fetch_members(pool: &PgPool, id: Uuid) -> Result<Vec<ProjectMember>> {
+	sqlx::query!(
+		r###"
+			SELECT "Users".id, username, role
+			FROM "ProjectMembers"
+			JOIN "Users" ON "Users".id = "ProjectMembers".user_id
+			WHERE project_id = $1
+			ORDER BY username
+		"###,
+		id
+	)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.map(|record| {
+		Ok(ProjectMember {
+			user_id: record.id,
+			username: record.username,
+			role: record
+				.role
+				.parse()
+				.map_err(|err| Error::ResponseError(StatusCode::INTERNAL_SERVER_ERROR, err))?,
+		})
+	})
+	.collect()
+}
+
+#[utoipa::path(
+	get,
+	path="/api/projects/{id}/members",
+	responses(
+		(status=200, body=[ProjectMember], description="The users that belong to the project, sorted by username"),
+		(status=401, description="No one is logged in"),
+		(status=404, description="The project with the given `id` was not found, or the user does not belong to it"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Project ID"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn list_members(
+	access: ProjectAccess<roles::ReadOnly>,
+	Extension(pool): Extension<PgPool>,
+) -> Result<Json<Vec<ProjectMember>>> {
+	fetch_members(&pool, access.project_id).await.map(Json)
+}
+
+#[utoipa::path(
+	put,
+	path="/api/projects/{id}/members/{user_id}",
+	request_body=MemberBody,
+	responses(
+		(status=200, body=[ProjectMember], description="The user was added to the project with the given role, or their role was changed"),
+		(status=401, description="No one is logged in"),
+		(status=403, description="The user is not an admin of the project"),
+		(status=404, description="The project or user was not found"),
+		(status=409, description="The change would leave the project without an admin"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Project ID"),
+		("user_id" = Uuid, Path, description = "User ID"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn set_member(
+	_access: ProjectAccess<roles::Admin>,
+	Extension(pool): Extension<PgPool>,
+	Path((id, user_id)): Path<(Uuid, Uuid)>,
+	Json(body): Json<MemberBody>,
+) -> Result<Json<Vec<ProjectMember>>> {
+	let mut transaction = pool.begin().await?;
+	lock_members(&mut transaction, id).await?;
+
+	let inserted = sqlx::query!(
+		r###"
+			INSERT INTO "ProjectMembers" (project_id, user_id, role)
+			SELECT "Projects".id, "Users".id, $3
+			FROM "Projects", "Users"
+			WHERE "Projects".id = $1 AND "Users".id = $2
+			ON CONFLICT (project_id, user_id) DO UPDATE SET role = excluded.role
+		"###,
+		id,
+		user_id,
+		body.role.as_str()
+	)
+	.execute(&mut *transaction)
+	.await?;
+
+	if inserted.rows_affected() == 0 {
+		return Err(Error::ResponseError(
+			StatusCode::NOT_FOUND,
+			fmt!("Project with ID {id} or user with ID {user_id} not found"),
+		));
+	}
+
+	ensure_admin(&mut transaction, id).await?;
+	transaction.commit().await?;
+
+	fetch_members(&pool, id).await.map(Json)
+}
+
+#[utoipa::path(
+	delete,
+	path="/api/projects/{id}/members/{user_id}",
+	responses(
+		(status=200, body=[ProjectMember], description="The user was removed from the project"),
+		(status=401, description="No one is logged in"),
+		(status=403, description="The user is not an admin of the project"),
+		(status=404, description="The user does not belong to the project"),
+		(status=409, description="The user is the only admin of the project"),
+	),
+	params(
+		("id" = Uuid, Path, description = "Project ID"),
+		("user_id" = Uuid, Path, description = "User ID"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn remove_member(
+	_access: ProjectAccess<roles::Admin>,
+	Extension(pool): Extension<PgPool>,
+	Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Vec<ProjectMember>>> {
+	let mut transaction = pool.begin().await?;
+	lock_members(&mut transaction, id).await?;
+
+	let deleted = sqlx::query!(
+		r#"DELETE FROM "ProjectMembers" WHERE project_id = $1 AND user_id = $2"#,
+		id,
+		user_id
+	)
+	.execute(&mut *transaction)
+	.await?;
+
+	if deleted.rows_affected() == 0 {
+		return Err(Error::ResponseError(
+			StatusCode::NOT_FOUND,
+			fmt!("User with ID {user_id} does not belong to project {id}"),
+		));
+	}
+
+	ensure_admin(&mut transaction, id).await?;
+	transaction.commit().await?;
+
+	fetch_members(&pool, id).await.map(Json)
+}
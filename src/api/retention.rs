@@ -0,0 +1,33 @@
+use axum::{extract::State, Json};
+use utoipa::ToSchema;
+
+use crate::{
+	api::{extractors::user::User, Store},
+	retention::{PruneReport, RetentionPolicy},
+};
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct RetentionStatus {
+	pub policy: RetentionPolicy,
+	/// What was deleted the last time the policy was enforced, or `null` if it hasn't
+	/// been enforced since the server started.
+	pub last_report: Option<PruneReport>,
+}
+
+#[utoipa::path(
+	get,
+	path="/api/retention",
+	responses(
+		(status=200, body=RetentionStatus, description="The retention policy of the server, and what was pruned the last time it was enforced."),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn get_retention(
+	_user: User,
+	State(store): State<Store>,
+) -> Json<RetentionStatus> {
+	Json(RetentionStatus {
+		policy: store.retention_policy.clone(),
+		last_report: store.last_prune.lock().clone(),
+	})
+}
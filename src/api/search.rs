@@ -0,0 +1,253 @@
+use std::collections::BTreeMap;
+
+use axum::{extract::Query, Extension, Json};
+use reqwest::StatusCode;
+use sqlx::{types::Json as SqlxJson, PgPool, QueryBuilder};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+	api::{
+		extractors::{client::ClientId, user::User},
+		log::{into_logs, LogRecord, LOG_COLUMNS},
+		types::{Log, LogFilter, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
+	},
+	prelude::*,
+};
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+	/// The search terms, which support quoted phrases, `or` and `-` to exclude a term.
+	#[param(example = "panic parser")]
+	pub q: String,
+	/// The maximum number of results to return, defaults to 100 and cannot be more than
+	/// 1000.
+	#[param(minimum = 1, maximum = 1000)]
+	pub limit: Option<i64>,
+	/// The number of results to skip, as returned in `next_offset` by the previous page.
+	#[param(minimum = 0)]
+	pub offset: Option<i64>,
+}
+
+/// A layer of a log's backtrace that matched the search, HTML-escaped with the matching
+/// terms wrapped in `<mark>` tags.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct LayerHighlight {
+	/// The position of the layer in the backtrace, starting at 0.
+	pub position: i32,
+	#[schema(example = "<mark>parse</mark>")]
+	pub name: String,
+	#[schema(example = "<mark>panic</mark>!(\"unexpected token\");")]
+	pub code: String,
+}
+
+/// The parts of a log that matched the search, HTML-escaped with the matching terms
+/// wrapped in `<mark>` tags, so that they can be rendered as HTML.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct Highlights {
+	#[schema(example = "a <mark>panic</mark> occurred")]
+	pub message: String,
+	pub snippet: BTreeMap<i32, String>,
+	pub layers: Vec<LayerHighlight>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct SearchResult {
+	pub log: Log,
+	/// How relevant the log is to the search, higher is more relevant.
+	pub rank: f32,
+	pub highlights: Highlights,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct SearchResults {
+	pub results: Vec<SearchResult>,
+	/// Passed as the `offset` parameter to get the next page of results, or `null` if
+	/// this is the last page.
+	pub next_offset: Option<i64>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct SearchRecord {
+	#[sqlx(flatten)]
+	log: LogRecord,
+	rank: f32,
+	message_highlight: String,
+	snippet_highlight: serde_json::Value,
+	layer_highlights: SqlxJson<Vec<LayerHighlight>>,
+}
+
+#[utoipa::path(
+	get,
+	path="/api/logs/search",
+	responses(
+		(status=200, body=SearchResults, description="The logs matching the search, most relevant first, with the matching parts highlighted."),
+		(status=400, description="The search was empty, or one of the query parameters was invalid"),
+		(status=401, description="No one is logged in"),
+	),
+	params(
+		("client-id" = Option<Uuid>, Header, description = "Client ID (optional)"),
+		SearchQuery,
+		LogFilter,
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn search_logs(
+	user: User,
+	client_id: Option<ClientId>,
+	Extension(pool): Extension<PgPool>,
+	Query(search): Query<SearchQuery>,
+	Query(mut filter): Query<LogFilter>,
+) -> Result<Json<SearchResults>> {
+	filter.project_ids = user.readable_projects();
+
+	if search.q.trim().is_empty() {
+		return Err(Error::ResponseError(
+			StatusCode::BAD_REQUEST,
+			"The search query `q` cannot be empty".into(),
+		));
+	}
+
+	let limit = search
+		.limit
+		.unwrap_or(DEFAULT_PAGE_SIZE)
+		.clamp(1, MAX_PAGE_SIZE);
+	let offset = search.offset.unwrap_or(0).max(0);
+
+	// logs can match either through their own message and snippet, or through any of
+	// the layers in their backtrace, both of which are looked up through their indexes.
+	// every match is ranked so that the page can be picked, but only the logs on that
+	// page go through the (comparatively expensive) highlighting.
+	let mut builder =
+		QueryBuilder::new(r"WITH query AS (SELECT websearch_to_tsquery('english', ");
+	builder.push_bind(search.q).push(
+		r#") AS query),
+		matching_logs AS (
+			SELECT "Logs".id
+			FROM "Logs", query
+			WHERE "Logs".search @@ query.query
+			UNION
+			SELECT "Logs".id
+			FROM "Layers"
+			JOIN "BacktracesLayers" ON "BacktracesLayers".layer_id = "Layers".id
+			JOIN "Logs" ON "Logs".backtrace_id = "BacktracesLayers".backtrace_id
+			CROSS JOIN query
+			WHERE "Layers".search @@ query.query
+		),
+		ranked AS (
+			SELECT
+				"#,
+	);
+	builder.push(LOG_COLUMNS).push(
+		r#",
+				ts_rank("Logs".search, query.query) + coalesce((
+					SELECT max(ts_rank("Layers".search, query.query))
+					FROM "BacktracesLayers"
+					JOIN "Layers" ON "Layers".id = "BacktracesLayers".layer_id
+					WHERE "BacktracesLayers".backtrace_id = "Logs".backtrace_id
+						AND "Layers".search @@ query.query
+				), 0) AS rank
+			FROM "Logs"
+			JOIN matching_logs ON matching_logs.id = "Logs".id
+			CROSS JOIN query
+			WHERE TRUE"#,
+	);
+
+	if let Some(ClientId(client_id)) = client_id {
+		builder
+			.push(r#" AND "Logs".client_id = "#)
+			.push_bind(client_id);
+	}
+
+	filter.push_conditions(&mut builder);
+
+	// only the fragments of a long message that matched are highlighted, whereas code is
+	// highlighted in full so that it can still be displayed as code.
+	builder
+		.push(r#" ORDER BY rank DESC, "Logs".date DESC, "Logs".id DESC LIMIT "#)
+		.push_bind(limit + 1)
+		.push(" OFFSET ")
+		.push_bind(offset)
+		.push(
+			r#"
+		)
+		SELECT
+			ranked.*,
+			search_highlight(
+				ranked.message, query.query,
+				'StartSel=<mark>, StopSel=</mark>, MaxFragments=3, MinWords=5, MaxWords=20, FragmentDelimiter=" … "'
+			) AS message_highlight,
+			coalesce((
+				SELECT jsonb_object_agg(line, search_highlight(
+					code, query.query, 'StartSel=<mark>, StopSel=</mark>, HighlightAll=true'
+				))
+				FROM json_each_text(ranked.snippet) AS snippet(line, code)
+			), '{}') AS snippet_highlight,
+			coalesce((
+				SELECT json_agg(json_build_object(
+					'position', "BacktracesLayers".position,
+					'name', search_highlight(
+						"Layers".name, query.query,
+						'StartSel=<mark>, StopSel=</mark>, HighlightAll=true'
+					),
+					'code', search_highlight(
+						"Layers".code, query.query,
+						'StartSel=<mark>, StopSel=</mark>, HighlightAll=true'
+					)
+				) ORDER BY "BacktracesLayers".position)
+				FROM "BacktracesLayers"
+				JOIN "Layers" ON "Layers".id = "BacktracesLayers".layer_id
+				WHERE "BacktracesLayers".backtrace_id = ranked.backtrace_id
+					AND "Layers".search @@ query.query
+			), '[]') AS layer_highlights
+		FROM ranked, query
+		ORDER BY ranked.rank DESC, ranked.date DESC, ranked.id DESC
+		"#,
+		);
+
+	let mut records = builder
+		.build_query_as::<SearchRecord>()
+		.fetch_all(&pool)
+		.await?;
+
+	let next_offset = if records.len() as i64 > limit {
+		records.truncate(limit as usize);
+		Some(offset + limit)
+	} else {
+		None
+	};
+
+	let mut details = Vec::with_capacity(records.len());
+	let mut log_records = Vec::with_capacity(records.len());
+	for record in records {
+		details.push((
+			record.rank,
+			record.message_highlight,
+			record.snippet_highlight,
+			record.layer_highlights.0,
+		));
+		log_records.push(record.log);
+	}
+
+	let logs = into_logs(&pool, log_records).await?;
+	let results = logs
+		.into_iter()
+		.zip(details)
+		.map(|(log, (rank, message, snippet, layers))| {
+			Ok(SearchResult {
+				log,
+				rank,
+				highlights: Highlights {
+					message,
+					snippet: serde_json::from_value(snippet)?,
+					layers,
+				},
+			})
+		})
+		.collect::<Result<Vec<_>>>()?;
+
+	Ok(Json(SearchResults {
+		results,
+		next_offset,
+	}))
+}
@@ -0,0 +1,236 @@
+use std::{
+	collections::{HashSet, VecDeque},
+	convert::Infallible,
+};
+
+use axum::{
+	extract::{Query, State},
+	http::HeaderMap,
+	response::sse::{Event, KeepAlive, Sse},
+	Extension,
+};
+use chrono::{SubsecRound, Utc};
+use futures_util::{stream, Stream};
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::{
+	api::{
+		extractors::{client::ClientId, user::User},
+		log::{fetch_logs_after, MAX_REPLAY_SIZE},
+		types::{Cursor, Log, LogFilter, LogPage},
+		Store,
+	},
+	prelude::*,
+	utils::log_socket::{DeletedLog, LogEvent, LogReceiver},
+	ws::protocol::Subscription,
+};
+
+/// The logs waiting to be sent on a single stream.
+struct LogStream {
+	pool: PgPool,
+	receiver: LogReceiver,
+	subscription: Subscription,
+	/// The position of the last log sent, which is also its event ID.
+	cursor: Cursor,
+	/// The IDs of the logs fetched from the database that may still be queued in the
+	/// receiver, so that those are only sent once.
+	replayed: HashSet<Uuid>,
+	/// Whether the missed logs are being replayed, in which case the next page of them is
+	/// fetched once the pending events have been sent.
+	replaying: bool,
+	pending: VecDeque<Event>,
+}
+
+impl LogStream {
+	fn push_log(&mut self, log: &Log) {
+		let cursor = Cursor::from(log);
+		let event = Event::default()
+			.event("log")
+			.id(cursor.to_string())
+			.json_data(log)
+			.expect("could not parse log into JSON");
+
+		self.cursor = cursor;
+		self.pending.push_back(event);
+	}
+
+	/// Queues a `deleted` event with the IDs of the `deleted` logs that may have been sent
+	/// on this stream.
+	fn push_deleted(&mut self, deleted: &[DeletedLog]) {
+		let ids = deleted
+			.iter()
+			.filter(|deleted| self.subscription.may_have_sent(deleted))
+			.map(|deleted| deleted.id)
+			.collect::<Vec<_>>();
+
+		if ids.is_empty() {
+			return;
+		}
+
+		let event = Event::default()
+			.event("deleted")
+			.json_data(serde_json::json!({ "ids": ids }))
+			.expect("could not parse deleted logs into JSON");
+
+		self.pending.push_back(event);
+	}
+
+	/// Starts replaying the logs matching the subscription that were sent after the last
+	/// log on this stream.
+	fn start_replay(&mut self) {
+		// the logs still queued in the receiver may be replayed as well.
+		self.replayed.clear();
+		self.replaying = true;
+	}
+
+	/// Queues the next page of missed logs. Once [`MAX_REPLAY_SIZE`] logs have been
+	/// replayed, the rest are skipped and a `truncated` event is queued instead.
+	async fn push_missed(&mut self) {
+		let page = fetch_logs_after(
+			&self.pool,
+			&self.subscription.client_ids,
+			&self.subscription.filter,
+			self.cursor,
+		)
+		.await;
+
+		let LogPage { logs, next_cursor } = match page {
+			Ok(page) => page,
+			Err(err) => {
+				tracing::error!("Could not fetch missed logs for stream: {err}");
+				self.replaying = false;
+				return;
+			}
+		};
+
+		self.replayed.extend(logs.iter().map(|log| log.id));
+
+		for log in &logs {
+			self.push_log(log);
+		}
+
+		if next_cursor.is_none() {
+			self.replaying = false;
+		} else if self.replayed.len() >= MAX_REPLAY_SIZE {
+			let event = Event::default()
+				.event("truncated")
+				.json_data(serde_json::json!({
+					"replayed": self.replayed.len(),
+					"cursor": self.cursor,
+				}))
+				.expect("could not parse notice into JSON");
+
+			self.pending.push_back(event);
+			self.replaying = false;
+		}
+	}
+
+	async fn next_event(&mut self) -> Option<Event> {
+		loop {
+			if let Some(event) = self.pending.pop_front() {
+				return Some(event);
+			}
+
+			if self.replaying {
+				self.push_missed().await;
+				continue;
+			}
+
+			match self.receiver.recv().await {
+				Ok(LogEvent::Created(log)) => {
+					if !self.replayed.remove(&log.id) && self.subscription.matches(&log) {
+						self.push_log(&log);
+					}
+				}
+				Ok(LogEvent::Deleted(deleted)) => self.push_deleted(&deleted),
+				Err(RecvError::Lagged(missed)) => {
+					tracing::warn!("Stream fell behind by {missed} logs, replaying them");
+
+					let event = Event::default()
+						.event("lagged")
+						.json_data(serde_json::json!({ "missed": missed }))
+						.expect("could not parse notice into JSON");
+
+					// the replayed logs are only fetched once the lagged event is sent.
+					self.pending.push_back(event);
+					self.start_replay();
+				}
+				Err(RecvError::Closed) => return None,
+			}
+		}
+	}
+}
+
+#[utoipa::path(
+	get,
+	path="/api/logs/stream",
+	responses(
+		(status=200, body=Log, content_type="text/event-stream", description="A stream of `log` events for every new log matching the given filters in the projects the user belongs to, each with the log as its data and its cursor as its ID. A `deleted` event, with the IDs of the logs in its `ids`, is sent when logs the stream may have sent are deleted. A `lagged` event is sent before any logs the stream missed for falling behind are sent again. At most 10000 missed logs are sent again, after which a `truncated` event is sent with the number of logs `replayed` and the `cursor` of the last one, from which the rest can be fetched with `GET /api/logs`."),
+		(status=400, description="One of the query parameters, or the `Last-Event-ID` header, was invalid"),
+		(status=401, description="No one is logged in"),
+	),
+	params(
+		("client-id" = Option<Uuid>, Header, description = "Client ID (optional)"),
+		("Last-Event-ID" = Option<String>, Header, description = "The ID of the last event received, to first receive every matching log sent since then"),
+		LogFilter,
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn stream_logs(
+	user: User,
+	client_id: Option<ClientId>,
+	Extension(pool): Extension<PgPool>,
+	State(store): State<Store>,
+	headers: HeaderMap,
+	Query(mut filter): Query<LogFilter>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+	filter.project_ids = user.readable_projects();
+
+	let last_event_id = headers
+		.get("last-event-id")
+		.map(|value| {
+			value
+				.to_str()
+				.map_err(|err| err.to_string())
+				.and_then(str::parse::<Cursor>)
+				.map_err(|err| Error::ResponseError(StatusCode::BAD_REQUEST, err))
+		})
+		.transpose()?;
+
+	// the receiver is subscribed before the missed logs are fetched, so that no logs are
+	// lost in between.
+	let mut log_stream = LogStream {
+		pool,
+		receiver: store.sender.subscribe(),
+		subscription: Subscription {
+			client_ids: client_id.map(|ClientId(id)| id).into_iter().collect(),
+			filter,
+		},
+		cursor: last_event_id.unwrap_or(Cursor {
+			date: Utc::now().trunc_subsecs(6),
+			id: Uuid::nil(),
+		}),
+		replayed: HashSet::new(),
+		replaying: false,
+		pending: VecDeque::new(),
+	};
+
+	if last_event_id.is_some() {
+		log_stream.start_replay();
+	}
+
+	let events = stream::unfold(log_stream, |mut log_stream| async move {
+		let event = log_stream.next_event().await?;
+
+		Some((Ok(event), log_stream))
+	});
+
+	// comments are sent as a heartbeat, so that proxies don't close idle streams and
+	// streams to clients that are gone are noticed once writing to them fails.
+	let keep_alive = KeepAlive::new().interval(store.heartbeat.ping_interval);
+
+	Ok(Sse::new(events).keep_alive(keep_alive))
+}
@@ -2,7 +2,7 @@ use utoipa::ToSchema;
 
 use crate::api::types::Layer;
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize, ToSchema)]
 pub struct Trace {
 	pub layers: Vec<Layer>,
 }
@@ -0,0 +1,499 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use sqlx::{types::ipnetwork::IpNetwork, Postgres, QueryBuilder};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{api::types::Log, prelude::fmt};
+
+/// The default number of logs returned in a single page.
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+/// The largest number of logs that can be requested in a single page.
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+/// Criteria that a log must match to be returned. Every field is optional, and fields
+/// that are present are combined with `AND`.
+#[derive(
+	Debug, Clone, Default, serde::Deserialize, serde::Serialize, IntoParams, ToSchema,
+)]
+#[into_params(parameter_in = Query)]
+pub struct LogFilter {
+	/// Only include logs sent to this project.
+	pub project_id: Option<Uuid>,
+	/// Only include logs sent to one of these projects. This can't be set by requests,
+	/// and is set to the projects that the user making the request can read instead.
+	#[serde(skip)]
+	pub project_ids: Option<Vec<Uuid>>,
+	/// Only include logs received at or after this time.
+	pub from: Option<DateTime<Utc>>,
+	/// Only include logs received before this time.
+	pub to: Option<DateTime<Utc>>,
+	/// Only include logs sent from this language.
+	#[param(example = "Rust")]
+	pub language: Option<String>,
+	/// Only include logs with this message type.
+	#[param(example = "&str")]
+	pub message_type: Option<String>,
+	/// Only include logs sent from this file.
+	#[param(example = "src/main.rs")]
+	pub file_name: Option<String>,
+	/// Only include logs sent from this IP address, or from within this CIDR range.
+	#[param(value_type = Option<String>, example = "192.168.0.0/16")]
+	#[schema(value_type = Option<String>, example = "192.168.0.0/16")]
+	pub sender: Option<IpNetwork>,
+	/// Only include logs that have (or don't have) warnings.
+	pub has_warnings: Option<bool>,
+	/// Only include logs with a message containing this text, ignoring case.
+	#[param(example = "panic")]
+	pub message: Option<String>,
+}
+
+impl LogFilter {
+	/// Whether none of the conditions that can be set by requests are present, so that
+	/// the filter matches every log the user can read.
+	pub fn is_empty(&self) -> bool {
+		self.project_id.is_none()
+			&& self.from.is_none()
+			&& self.to.is_none()
+			&& self.language.is_none()
+			&& self.message_type.is_none()
+			&& self.file_name.is_none()
+			&& self.sender.is_none()
+			&& self.has_warnings.is_none()
+			&& self.message.is_none()
+	}
+
+	/// Whether `log` matches every condition of this filter, for logs that haven't been
+	/// read from the database. This must be kept in sync with [`Self::push_conditions`].
+	pub fn matches(&self, log: &Log) -> bool {
+		let is_sent_by = |sender: IpNetwork| {
+			log
+				.received_from
+				.is_some_and(|received_from| sender.contains(received_from.ip()))
+		};
+		let contains_message =
+			|message: &String| log.message.to_lowercase().contains(&message.to_lowercase());
+
+		self
+			.project_id
+			.is_none_or(|project_id| log.project_id == Some(project_id))
+			&& self.project_ids.as_ref().is_none_or(|project_ids| {
+				log
+					.project_id
+					.is_some_and(|project_id| project_ids.contains(&project_id))
+			}) && self.from.is_none_or(|from| log.date >= from)
+			&& self.to.is_none_or(|to| log.date < to)
+			&& self
+				.language
+				.as_ref()
+				.is_none_or(|language| &log.language == language)
+			&& self
+				.message_type
+				.as_ref()
+				.is_none_or(|message_type| &log.message_type == message_type)
+			&& self
+				.file_name
+				.as_ref()
+				.is_none_or(|file_name| &log.file_name == file_name)
+			&& self.sender.is_none_or(is_sent_by)
+			&& self
+				.has_warnings
+				.is_none_or(|has_warnings| log.warnings.is_empty() != has_warnings)
+			&& self.message.as_ref().is_none_or(contains_message)
+	}
+
+	/// Appends the conditions of this filter to `builder`, each one prefixed with `AND`,
+	/// so the query being built must already have a `WHERE` clause. Columns are
+	/// referenced through the `"Logs"` table name.
+	pub fn push_conditions(&self, builder: &mut QueryBuilder<'_, Postgres>) {
+		if let Some(project_id) = self.project_id {
+			builder
+				.push(r#" AND "Logs".project_id = "#)
+				.push_bind(project_id);
+		}
+
+		if let Some(project_ids) = &self.project_ids {
+			builder
+				.push(r#" AND "Logs".project_id = ANY("#)
+				.push_bind(project_ids.clone())
+				.push(")");
+		}
+
+		if let Some(from) = self.from {
+			builder
+				.push(r#" AND "Logs".date >= "#)
+				.push_bind(from.naive_utc());
+		}
+
+		if let Some(to) = self.to {
+			builder
+				.push(r#" AND "Logs".date < "#)
+				.push_bind(to.naive_utc());
+		}
+
+		if let Some(language) = &self.language {
+			builder
+				.push(r#" AND "Logs".language = "#)
+				.push_bind(language.clone());
+		}
+
+		if let Some(message_type) = &self.message_type {
+			builder
+				.push(r#" AND "Logs".message_type = "#)
+				.push_bind(message_type.clone());
+		}
+
+		if let Some(file_name) = &self.file_name {
+			builder
+				.push(r#" AND "Logs".file_name = "#)
+				.push_bind(file_name.clone());
+		}
+
+		if let Some(sender) = self.sender {
+			builder
+				.push(r#" AND "Logs".received_from <<= "#)
+				.push_bind(sender);
+		}
+
+		match self.has_warnings {
+			Some(true) => builder.push(r#" AND cardinality("Logs".warnings) > 0"#),
+			Some(false) => builder.push(r#" AND cardinality("Logs".warnings) = 0"#),
+			None => builder,
+		};
+
+		if let Some(message) = &self.message {
+			let escaped = message
+				.replace('\\', "\\\\")
+				.replace('%', "\\%")
+				.replace('_', "\\_");
+
+			builder
+				.push(r#" AND "Logs".message ILIKE "#)
+				.push_bind(fmt!("%{escaped}%"));
+		}
+	}
+}
+
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	Default,
+	PartialEq,
+	Eq,
+	serde::Deserialize,
+	serde::Serialize,
+	ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+	Asc,
+	#[default]
+	Desc,
+}
+
+/// An opaque position in a list of logs sorted by date and then ID, used to fetch the
+/// page of logs that comes after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cursor {
+	pub date: DateTime<Utc>,
+	pub id: Uuid,
+}
+
+impl From<&Log> for Cursor {
+	fn from(log: &Log) -> Self {
+		Self {
+			date: log.date,
+			id: log.id,
+		}
+	}
+}
+
+impl Display for Cursor {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}_{}", self.date.timestamp_micros(), self.id.simple())
+	}
+}
+
+impl FromStr for Cursor {
+	type Err = String;
+
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		let invalid = || fmt!("'{value}' is not a valid cursor");
+
+		let (micros, id) = value.split_once('_').ok_or_else(invalid)?;
+		let micros = micros.parse().map_err(|_| invalid())?;
+
+		Ok(Self {
+			date: DateTime::from_timestamp_micros(micros).ok_or_else(invalid)?,
+			id: id.parse().map_err(|_| invalid())?,
+		})
+	}
+}
+
+impl TryFrom<String> for Cursor {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		value.parse()
+	}
+}
+
+impl From<Cursor> for String {
+	fn from(cursor: Cursor) -> Self {
+		cursor.to_string()
+	}
+}
+
+/// Controls which page of logs is returned, and in what order.
+#[derive(
+	Debug, Clone, Copy, Default, serde::Deserialize, serde::Serialize, IntoParams,
+)]
+#[into_params(parameter_in = Query)]
+pub struct Page {
+	/// The maximum number of logs to return, defaults to 100 and cannot be more than
+	/// 1000.
+	#[param(minimum = 1, maximum = 1000)]
+	pub limit: Option<i64>,
+	/// Only return logs that come after this cursor, as returned in `next_cursor` by the
+	/// previous page.
+	#[param(value_type = Option<String>)]
+	pub cursor: Option<Cursor>,
+	/// Whether to return the oldest (`asc`) or newest (`desc`, the default) logs first.
+	#[param(inline)]
+	pub order: Option<SortOrder>,
+}
+
+impl Page {
+	pub fn limit(&self) -> i64 {
+		self
+			.limit
+			.unwrap_or(DEFAULT_PAGE_SIZE)
+			.clamp(1, MAX_PAGE_SIZE)
+	}
+
+	/// Appends the cursor condition, ordering and limit of this page to `builder`. This
+	/// must be called after every condition of the query has been pushed, and fetches one
+	/// more row than the limit so that the caller can tell whether there is another page.
+	pub fn push_ordering(&self, builder: &mut QueryBuilder<'_, Postgres>) {
+		let order = self.order.unwrap_or_default();
+
+		if let Some(cursor) = self.cursor {
+			builder.push(match order {
+				SortOrder::Asc => r#" AND ("Logs".date, "Logs".id) > ("#,
+				SortOrder::Desc => r#" AND ("Logs".date, "Logs".id) < ("#,
+			});
+			builder
+				.push_bind(cursor.date.naive_utc())
+				.push(", ")
+				.push_bind(cursor.id)
+				.push(")");
+		}
+
+		builder.push(match order {
+			SortOrder::Asc => r#" ORDER BY "Logs".date ASC, "Logs".id ASC"#,
+			SortOrder::Desc => r#" ORDER BY "Logs".date DESC, "Logs".id DESC"#,
+		});
+
+		builder.push(" LIMIT ").push_bind(self.limit() + 1);
+	}
+}
+
+/// A single page of logs, along with the cursor needed to fetch the next one.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct LogPage {
+	pub logs: Vec<Log>,
+	/// Passed as the `cursor` parameter to get the next page, or `null` if this is the
+	/// last page.
+	#[schema(example = "1703382317000000_67e5504410b1426f9247bb680e5fe0c8")]
+	pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+
+	use chrono::TimeZone;
+
+	use super::*;
+	use crate::api::types::Trace;
+
+	fn log() -> Log {
+		Log {
+			id: Uuid::new_v4(),
+			client_id: Uuid::new_v4(),
+			project_id: Some(Uuid::new_v4()),
+			message: "Something PANICKED here".into(),
+			message_type: "&str".into(),
+			language: "Rust".into(),
+			snippet: BTreeMap::new(),
+			file_name: "src/main.rs".into(),
+			line_number: 1,
+			backtrace: Trace::default(),
+			warnings: Vec::new(),
+			date: Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap(),
+			received_from: Some("192.168.1.20/32".parse().unwrap()),
+		}
+	}
+
+	#[test]
+	fn cursor_round_trips() {
+		let cursor = Cursor {
+			date: Utc.timestamp_micros(1_703_382_317_123_456).unwrap(),
+			id: Uuid::new_v4(),
+		};
+
+		assert_eq!(cursor.to_string().parse::<Cursor>(), Ok(cursor));
+
+		let json = serde_json::to_string(&cursor).unwrap();
+		assert_eq!(serde_json::from_str::<Cursor>(&json).unwrap(), cursor);
+	}
+
+	#[test]
+	fn cursor_rejects_invalid_input() {
+		let id = Uuid::new_v4().simple();
+
+		for invalid in [
+			String::new(),
+			"1703382317000000".into(),
+			fmt!("abc_{id}"),
+			"1703382317000000_not-a-uuid".into(),
+			fmt!("{}_{id}", i64::MAX),
+		] {
+			assert!(invalid.parse::<Cursor>().is_err(), "{invalid} was accepted");
+		}
+	}
+
+	#[test]
+	fn empty_filter_matches_everything() {
+		assert!(LogFilter::default().matches(&log()));
+	}
+
+	#[test]
+	fn filter_of_only_readable_projects_is_empty() {
+		let mut filter = LogFilter {
+			project_ids: Some(vec![Uuid::new_v4()]),
+			..LogFilter::default()
+		};
+		assert!(filter.is_empty());
+
+		filter.has_warnings = Some(false);
+		assert!(!filter.is_empty());
+	}
+
+	#[test]
+	fn matches_projects() {
+		let log = log();
+		let other = Uuid::new_v4();
+
+		let filter = |project_id, project_ids| LogFilter {
+			project_id,
+			project_ids,
+			..LogFilter::default()
+		};
+
+		assert!(filter(log.project_id, None).matches(&log));
+		assert!(!filter(Some(other), None).matches(&log));
+		assert!(filter(None, Some(vec![other, log.project_id.unwrap()])).matches(&log));
+		assert!(!filter(None, Some(vec![other])).matches(&log));
+		assert!(!filter(None, Some(Vec::new())).matches(&log));
+	}
+
+	#[test]
+	fn matches_dates() {
+		let log = log();
+		let second = chrono::Duration::seconds(1);
+
+		let filter = |from, to| LogFilter {
+			from,
+			to,
+			..LogFilter::default()
+		};
+
+		// `from` is inclusive and `to` is exclusive.
+		assert!(filter(Some(log.date), None).matches(&log));
+		assert!(!filter(Some(log.date + second), None).matches(&log));
+		assert!(filter(None, Some(log.date + second)).matches(&log));
+		assert!(!filter(None, Some(log.date)).matches(&log));
+	}
+
+	#[test]
+	fn matches_exact_fields() {
+		let log = log();
+
+		let language = |language: &str| LogFilter {
+			language: Some(language.into()),
+			..LogFilter::default()
+		};
+		assert!(language("Rust").matches(&log));
+		assert!(!language("rust").matches(&log));
+
+		let message_type = |message_type: &str| LogFilter {
+			message_type: Some(message_type.into()),
+			..LogFilter::default()
+		};
+		assert!(message_type("&str").matches(&log));
+		assert!(!message_type("String").matches(&log));
+
+		let file_name = |file_name: &str| LogFilter {
+			file_name: Some(file_name.into()),
+			..LogFilter::default()
+		};
+		assert!(file_name("src/main.rs").matches(&log));
+		assert!(!file_name("main.rs").matches(&log));
+	}
+
+	#[test]
+	fn matches_sender_ranges() {
+		let mut log = log();
+
+		let sender = |sender: &str| LogFilter {
+			sender: Some(sender.parse().unwrap()),
+			..LogFilter::default()
+		};
+
+		assert!(sender("192.168.1.20").matches(&log));
+		assert!(sender("192.168.0.0/16").matches(&log));
+		assert!(!sender("192.168.1.21").matches(&log));
+		assert!(!sender("10.0.0.0/8").matches(&log));
+
+		log.received_from = None;
+		assert!(!sender("0.0.0.0/0").matches(&log));
+	}
+
+	#[test]
+	fn matches_warnings() {
+		let mut log = log();
+
+		let has_warnings = |has_warnings| LogFilter {
+			has_warnings: Some(has_warnings),
+			..LogFilter::default()
+		};
+
+		assert!(has_warnings(false).matches(&log));
+		assert!(!has_warnings(true).matches(&log));
+
+		log.warnings.push("No debug symbols".into());
+		assert!(has_warnings(true).matches(&log));
+		assert!(!has_warnings(false).matches(&log));
+	}
+
+	#[test]
+	fn matches_message_ignoring_case() {
+		let log = log();
+
+		let message = |message: &str| LogFilter {
+			message: Some(message.into()),
+			..LogFilter::default()
+		};
+
+		assert!(message("panicked").matches(&log));
+		assert!(message("PANICKED HERE").matches(&log));
+		assert!(message("").matches(&log));
+		assert!(!message("unwrap").matches(&log));
+		// unlike in the query, wildcards have no special meaning.
+		assert!(!message("%").matches(&log));
+	}
+}
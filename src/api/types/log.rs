@@ -15,6 +15,13 @@ fn _default_received_from() -> Option<IpNetwork> {
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema, sqlx::FromRow)]
 pub struct Log {
 	pub id: Uuid,
+	/// The ID of the client that sent the log.
+	#[serde(skip_deserializing)]
+	#[schema(example = "3f2c8a4e-5b1d-4c9e-8f7a-2d6b0e1c9a54")]
+	pub client_id: Uuid,
+	/// The ID of the project the client belonged to when it sent the log, if any.
+	#[serde(skip_deserializing)]
+	pub project_id: Option<Uuid>,
 	#[schema(example = "hello")]
 	pub message: String,
 	#[schema(example = "&str")]
@@ -1,7 +1,9 @@
 mod backtrace;
+mod filter;
 mod layer;
 mod log;
 
 pub use backtrace::*;
+pub use filter::*;
 pub use layer::*;
 pub use log::*;
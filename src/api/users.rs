@@ -0,0 +1,214 @@
+use axum::{extract::Path, Extension, Json};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+	api::{
+		auth::hash_password,
+		extractors::user::{ServerAdmin, User},
+		Response,
+	},
+	prelude::*,
+};
+
+/// The shortest password that can be given to a user.
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// A user account, as seen by server administrators.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct UserInfo {
+	pub id: Uuid,
+	#[schema(example = "alice")]
+	pub username: String,
+	pub is_admin: bool,
+	pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct NewUser {
+	/// The name the user logs in with, which must be unique.
+	#[schema(example = "alice")]
+	pub username: String,
+	/// At least 8 characters long.
+	pub password: String,
+	/// Whether the user is an administrator of the whole server. The first user is
+	/// always an administrator.
+	#[serde(default)]
+	pub is_admin: bool,
+}
+
+/// Creates a user account. The first account can be created by anyone, and is made an
+/// administrator of the server, after which only administrators can create accounts.
+#[utoipa::path(
+	post,
+	path="/api/users",
+	request_body=NewUser,
+	responses(
+		(status=200, body=UserInfo, description="The new user"),
+		(status=400, description="The username was empty, or the password was too short"),
+		(status=401, description="Users already exist, and no one is logged in"),
+		(status=403, description="The user logged in is not a server administrator"),
+		(status=409, description="A user with the same username already exists"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn create_user(
+	user: Option<User>,
+	Extension(pool): Extension<PgPool>,
+	Json(new_user): Json<NewUser>,
+) -> Result<Json<UserInfo>> {
+	let username = new_user.username.trim();
+
+	if username.is_empty() {
+		return Err(Error::ResponseError(
+			StatusCode::BAD_REQUEST,
+			"Username cannot be empty".into(),
+		));
+	}
+
+	if new_user.password.chars().count() < MIN_PASSWORD_LENGTH {
+		return Err(Error::ResponseError(
+			StatusCode::BAD_REQUEST,
+			fmt!("Password must be at least {MIN_PASSWORD_LENGTH} characters long"),
+		));
+	}
+
+	let password_hash = hash_password(new_user.password).await?;
+
+	let mut transaction = pool.begin().await?;
+
+	// the table is locked so that two first users can't be created at once.
+	sqlx::query!(r#"LOCK TABLE "Users" IN EXCLUSIVE MODE"#)
+		.execute(&mut *transaction)
+		.await?;
+
+	let is_first =
+		sqlx::query_scalar!(r#"SELECT NOT EXISTS (SELECT 1 FROM "Users") AS "is_first!""#)
+			.fetch_one(&mut *transaction)
+			.await?;
+
+	if !is_first {
+		match user {
+			Some(user) if user.is_admin => {}
+			Some(_) => {
+				return Err(Error::ResponseError(
+					StatusCode::FORBIDDEN,
+					"Only server administrators can create users".into(),
+				))
+			}
+			None => {
+				return Err(Error::ResponseError(
+					StatusCode::UNAUTHORIZED,
+					"You need to log in to do this".into(),
+				))
+			}
+		}
+	}
+
+	let record = sqlx::query!(
+		r###"
+			INSERT INTO "Users" (username, password_hash, is_admin)
+			VALUES ($1, $2, $3)
+			ON CONFLICT (username) DO NOTHING
+			RETURNING id, username, is_admin, created_at
+		"###,
+		username,
+		password_hash,
+		is_first || new_user.is_admin
+	)
+	.fetch_optional(&mut *transaction)
+	.await?
+	.ok_or_else(|| {
+		Error::ResponseError(
+			StatusCode::CONFLICT,
+			fmt!("A user named '{username}' already exists"),
+		)
+	})?;
+
+	transaction.commit().await?;
+
+	Ok(Json(UserInfo {
+		id: record.id,
+		username: record.username,
+		is_admin: record.is_admin,
+		created_at: record.created_at.and_utc(),
+	}))
+}
+
+#[utoipa::path(
+	get,
+	path="/api/users",
+	responses(
+		(status=200, body=[UserInfo], description="Every user, sorted by username"),
+		(status=401, description="No one is logged in"),
+		(status=403, description="The user logged in is not a server administrator"),
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn list_users(
+	_admin: ServerAdmin,
+	Extension(pool): Extension<PgPool>,
+) -> Result<Json<Vec<UserInfo>>> {
+	let users = sqlx::query!(
+		r#"SELECT id, username, is_admin, created_at FROM "Users" ORDER BY username"#
+	)
+	.fetch_all(&pool)
+	.await?
+	.into_iter()
+	.map(|record| UserInfo {
+		id: record.id,
+		username: record.username,
+		is_admin: record.is_admin,
+		created_at: record.created_at.and_utc(),
+	})
+	.collect();
+
+	Ok(Json(users))
+}
+
+#[utoipa::path(
+	delete,
+	path="/api/users/{id}",
+	responses(
+		(status=200, body=Response, description="The user was deleted, along with their sessions and memberships"),
+		(status=400, description="The user tried to delete themselves"),
+		(status=401, description="No one is logged in"),
+		(status=403, description="The user logged in is not a server administrator"),
+		(status=404, description="The user with the given `id` was not found"),
+	),
+	params(
+		("id" = Uuid, Path, description = "User ID")
+	),
+)]
+#[axum_macros::debug_handler]
+pub async fn delete_user(
+	ServerAdmin(admin): ServerAdmin,
+	Extension(pool): Extension<PgPool>,
+	Path(id): Path<Uuid>,
+) -> Result<Json<Response>> {
+	if admin.id == id {
+		return Err(Error::ResponseError(
+			StatusCode::BAD_REQUEST,
+			"You cannot delete yourself".into(),
+		));
+	}
+
+	let deleted = sqlx::query!(r#"DELETE FROM "Users" WHERE id = $1"#, id)
+		.execute(&pool)
+		.await?;
+
+	if deleted.rows_affected() == 0 {
+		return Err(Error::ResponseError(
+			StatusCode::NOT_FOUND,
+			fmt!("User with ID {id} not found"),
+		));
+	}
+
+	Ok(Json(Response {
+		message: fmt!("User with ID {id} was deleted"),
+		datetime: Utc::now(),
+	}))
+}
@@ -0,0 +1,299 @@
+//! A gRPC listener that accepts logs sent with `CodeCTRL`'s protocol, so that
+//! producers written for `CodeCTRL` can send logs to `TraceCTRL` before they are moved
+//! over to the REST API. Logs sent here are stored and broadcast the same way as those
+//! sent to `/api/log`, and are authenticated with an API key sent in the `authorization`
+//! metadata as `Bearer <key>`.
+//!
+//! Producers written for `CodeCTRL` don't send any metadata, so the key set by the
+//! `TC_GRPC_API_KEY` environment variable is used for requests without an
+//! `authorization` entry, which lets them send logs without being changed. Their logs
+//! all belong to the client of that key, and revoking it stops them being accepted.
+//! Without the variable, every request must send its own key.
+
+use std::net::SocketAddr;
+
+use sqlx::PgPool;
+use tonic::{
+	metadata::MetadataMap,
+	transport::Server,
+	Request,
+	Response,
+	Status,
+	Streaming,
+};
+
+use crate::{
+	api::{
+		extractors::api_key::ApiKey,
+		log::{create_logs, socket_addr_to_ip_network, LogBody, MAX_BATCH_SIZE},
+		types::{Layer, Trace},
+		Store,
+	},
+	prelude::*,
+	utils::env::parse_var,
+};
+
+/// The code generated from the protocol definitions in `proto/codectrl`.
+#[allow(clippy::pedantic)]
+mod proto {
+	pub mod codectrl {
+		pub mod data {
+			pub mod backtrace_data {
+				tonic::include_proto!("codectrl.data.backtrace_data");
+			}
+
+			pub mod log {
+				tonic::include_proto!("codectrl.data.log");
+			}
+		}
+
+		pub mod logs_service {
+			tonic::include_proto!("codectrl.logs_service");
+		}
+	}
+}
+
+use proto::codectrl::{
+	data::log::Log as CodeCtrlLog,
+	logs_service::{
+		log_server_server::{LogServer, LogServerServer},
+		RequestResult,
+		RequestStatus,
+	},
+};
+
+/// The port `CodeCTRL` listened for logs on, so that existing producers only need an
+/// API key to send logs to `TraceCTRL`.
+const DEFAULT_PORT: u16 = 3002;
+
+/// Where the gRPC listener accepts logs, as set by the `TC_GRPC_PORT` environment
+/// variable. Setting `TC_GRPC_ENABLED` to `false` turns the listener off.
+#[derive(Debug, Clone)]
+pub struct GrpcConfig {
+	pub addr: Option<SocketAddr>,
+	/// The API key that requests without an `authorization` entry in their metadata are
+	/// authenticated with, as set by the `TC_GRPC_API_KEY` environment variable.
+	pub default_api_key: Option<String>,
+}
+
+impl GrpcConfig {
+	pub fn from_env() -> Self {
+		let enabled = parse_var("TC_GRPC_ENABLED").unwrap_or(true);
+		let port = parse_var("TC_GRPC_PORT").unwrap_or(DEFAULT_PORT);
+
+		Self {
+			addr: enabled.then(|| SocketAddr::from(([0, 0, 0, 0], port))),
+			default_api_key: parse_var("TC_GRPC_API_KEY"),
+		}
+	}
+}
+
+/// Converts a line or column number sent by a producer into the type it is stored as,
+/// returning why the log was rejected if it is too large to be stored.
+fn to_line_number(number: u32, name: &str) -> std::result::Result<i32, String> {
+	i32::try_from(number)
+		.map_err(|_| fmt!("The {name} {number} is too large, the most is {}", i32::MAX))
+}
+
+impl TryFrom<CodeCtrlLog> for LogBody {
+	type Error = String;
+
+	fn try_from(log: CodeCtrlLog) -> std::result::Result<Self, Self::Error> {
+		let layers = log
+			.stack
+			.into_iter()
+			.map(|frame| {
+				Ok(Layer {
+					line_number: to_line_number(frame.line_number, "line number")?,
+					column_number: to_line_number(frame.column_number, "column number")?,
+					code: frame.code,
+					name: frame.name,
+					file_path: (!frame.file_path.is_empty()).then_some(frame.file_path),
+				})
+			})
+			.collect::<std::result::Result<_, String>>()?;
+
+		let snippet = log
+			.code_snippet
+			.into_iter()
+			.map(|(line, code)| Ok((to_line_number(line, "snippet line")?, code)))
+			.collect::<std::result::Result<_, String>>()?;
+
+		Ok(Self {
+			message: log.message,
+			message_type: log.message_type,
+			language: log.language,
+			backtrace: Trace { layers },
+			snippet,
+			line_number: to_line_number(log.line_number, "line number")?,
+			file_name: log.file_name,
+			warnings: log.warnings,
+		})
+	}
+}
+
+// the details of errors are only logged, as they can describe the database to anyone
+// with an API key.
+impl From<Error> for Status {
+	fn from(err: Error) -> Self {
+		tracing::error!("An error occurred: {err}");
+
+		Status::internal("Internal server error")
+	}
+}
+
+fn confirmed(created: usize) -> Response<RequestResult> {
+	Response::new(RequestResult {
+		message: fmt!("Created {created} logs"),
+		status: RequestStatus::Confirmed.into(),
+	})
+}
+
+/// Implements `CodeCTRL`'s `LogServer` service on top of the same storage as the REST
+/// API.
+struct CodeCtrlService {
+	pool: PgPool,
+	store: Store,
+	default_api_key: Option<String>,
+}
+
+impl CodeCtrlService {
+	/// Authenticates the client that made a request by the API key in its `metadata`,
+	/// or by the default API key if it has no `authorization` entry.
+	async fn authenticate(
+		&self,
+		metadata: &MetadataMap,
+	) -> std::result::Result<ApiKey, Status> {
+		let key = match metadata.get("authorization") {
+			Some(value) => value
+				.to_str()
+				.ok()
+				.and_then(|value| value.strip_prefix("Bearer "))
+				.ok_or_else(|| Status::unauthenticated("`authorization` metadata is invalid"))?,
+			None => self
+				.default_api_key
+				.as_deref()
+				.ok_or_else(|| Status::unauthenticated("`authorization` metadata is missing"))?,
+		};
+
+		ApiKey::authenticate(&self.pool, key)
+			.await
+			.map_err(|err| Status::from(Error::from(err)))?
+			.ok_or_else(|| Status::unauthenticated("API key is invalid or has been revoked"))
+	}
+
+	/// Stores `logs` sent by the client with `api_key` in a request made from `addr`,
+	/// returning how many were stored.
+	async fn create(
+		&self,
+		api_key: ApiKey,
+		addr: Option<SocketAddr>,
+		logs: Vec<CodeCtrlLog>,
+	) -> std::result::Result<usize, Status> {
+		let addr = addr.ok_or_else(|| Status::internal("Could not get the peer address"))?;
+		let bodies = logs
+			.into_iter()
+			.map(LogBody::try_from)
+			.collect::<std::result::Result<_, String>>()
+			.map_err(Status::invalid_argument)?;
+
+		let logs = create_logs(
+			&self.pool,
+			&self.store,
+			api_key.client_id,
+			socket_addr_to_ip_network(&addr),
+			bodies,
+		)
+		.await?;
+
+		Ok(logs.len())
+	}
+}
+
+#[tonic::async_trait]
+impl LogServer for CodeCtrlService {
+	async fn send_log(
+		&self,
+		request: Request<CodeCtrlLog>,
+	) -> std::result::Result<Response<RequestResult>, Status> {
+		let api_key = self.authenticate(request.metadata()).await?;
+		let addr = request.remote_addr();
+
+		let created = self
+			.create(api_key, addr, vec![request.into_inner()])
+			.await?;
+
+		Ok(confirmed(created))
+	}
+
+	async fn send_logs(
+		&self,
+		request: Request<Streaming<CodeCtrlLog>>,
+	) -> std::result::Result<Response<RequestResult>, Status> {
+		let api_key = self.authenticate(request.metadata()).await?;
+		let addr = request.remote_addr();
+		let mut stream = request.into_inner();
+		let mut logs = Vec::new();
+		let mut created = 0;
+
+		// streams can go on for as long as the producer runs, so the logs are stored in
+		// batches as they arrive rather than all at once at the end.
+		let received = async {
+			while let Some(log) = stream.message().await? {
+				logs.push(log);
+
+				if logs.len() == MAX_BATCH_SIZE {
+					created += self
+						.create(api_key, addr, std::mem::take(&mut logs))
+						.await?;
+				}
+			}
+
+			created += self.create(api_key, addr, logs).await?;
+
+			Ok::<_, Status>(())
+		}
+		.await;
+
+		match received {
+			Ok(()) => Ok(confirmed(created)),
+			Err(status) if created == 0 => Err(status),
+			// the batches stored before the failure are kept, so the producer is told how
+			// many there were rather than only that the request failed, which would lead it
+			// to send them again.
+			Err(status) => Ok(Response::new(RequestResult {
+				message: fmt!(
+					"Created {created} logs before failing: {}",
+					status.message()
+				),
+				status: RequestStatus::Error.into(),
+			})),
+		}
+	}
+}
+
+/// Runs the gRPC listener at `config.addr` for as long as the server is running, doing
+/// nothing if it is turned off.
+pub async fn serve(config: GrpcConfig, pool: PgPool, store: Store) {
+	let Some(addr) = config.addr else {
+		return;
+	};
+
+	tracing::info!("Listening for CodeCTRL logs on grpc://{addr}");
+
+	if config.default_api_key.is_some() {
+		tracing::info!(
+			"Accepting CodeCTRL logs without an API key as TC_GRPC_API_KEY's client"
+		);
+	}
+
+	let service = LogServerServer::new(CodeCtrlService {
+		pool,
+		store,
+		default_api_key: config.default_api_key,
+	});
+
+	if let Err(err) = Server::builder().add_service(service).serve(addr).await {
+		tracing::error!("Could not run the gRPC listener: {err}");
+	}
+}
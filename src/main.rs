@@ -4,19 +4,25 @@
 	clippy::enum_variant_names,
 	clippy::cast_possible_truncation,
 	clippy::cast_possible_wrap,
+	clippy::cast_sign_loss,
 	clippy::too_many_lines
 )]
 
 mod api;
 mod error;
+mod grpc;
 mod prelude;
+mod retention;
 mod utils;
 mod ws;
 
 use crate::{
-	api::{ApiDoc, ApiRouter},
+	api::{auth::SessionConfig, ApiDoc, ApiRouter, Store},
+	grpc::GrpcConfig,
 	prelude::*,
-	utils::{arctex::ArcTex, log_socket::new_log_socket, peer_map::PeerMap, W},
+	retention::RetentionPolicy,
+	utils::{arctex::ArcTex, log_socket::new_log_socket, recent_logs::RecentLogs, W},
+	ws::Heartbeat,
 };
 
 #[cfg(debug_assertions)]
@@ -35,7 +41,6 @@ use axum::{
 	Router,
 };
 use sqlx::postgres::PgPoolOptions;
-use tokio::net::TcpListener;
 #[cfg(feature = "save_docs")]
 use tokio::{fs::File, io::AsyncWriteExt};
 use tower::ServiceExt;
@@ -125,13 +130,13 @@ async fn file_handler(uri: Uri) -> Result<Response> {
 // If the application was compiled in debug mode:
 // - The application starts a vite server as a child process, running the front-end
 // - It then forwards every request made to itself to the vite server
-// - It spins up the websocket server, so that the front-end can get the logs and anything
-//   else that may be sent through the pipe via websockets.
+// - It serves websockets at `/ws` on the same router as the api, so that the front-end
+//   can get the logs and anything else that may be sent through the pipe.
 //
 // If the application was compiled in release mode:
 // - The application serves the static files compiled by vite
-// - It spins up the websocket server, so that the front-end can get the logs and anything
-//   else that may be sent through the pipe via websockets.
+// - It serves websockets at `/ws` on the same router as the api, so that the front-end
+//   can get the logs and anything else that may be sent through the pipe.
 #[tokio::main]
 async fn main() {
 	dotenv::dotenv().ok();
@@ -166,9 +171,32 @@ async fn main() {
 		Router::new().fallback(get(file_handler))
 	};
 
-	// create a new log sender and receiver for communicating to/from the websocket server
-	// and the api
+	// create a new log sender and receiver for communicating between the api and the
+	// websockets connected to `/ws`
 	let (tx, _) = new_log_socket();
+	let retention_policy = RetentionPolicy::from_env();
+	let recent_logs = RecentLogs::from_env(&retention_policy);
+	let store = Store::new(
+		tx.clone(),
+		recent_logs,
+		retention_policy.clone(),
+		Heartbeat::from_env(),
+		SessionConfig::from_env(),
+	);
+
+	// old logs are pruned in the background for as long as the server is running.
+	tokio::spawn(retention::run(
+		retention_policy,
+		pool.clone(),
+		store.clone(),
+	));
+
+	// producers still using CodeCTRL's gRPC protocol send their logs to a separate port.
+	tokio::spawn(grpc::serve(
+		GrpcConfig::from_env(),
+		pool.clone(),
+		store.clone(),
+	));
 
 	#[cfg(feature = "save_docs")]
 	{
@@ -189,7 +217,7 @@ async fn main() {
 	// we can use this as an endpoint for any additional actions the front-end may
 	// need besides just receiving logs.
 	let router = Router::new()
-		.nest("/api", ApiRouter::new_router(tx.clone(), pool))
+		.merge(ApiRouter::new_router(store, pool))
 		.merge(SwaggerUi::new("/docs/swagger").url("/docs/openapi.json", ApiDoc::openapi()))
 		.merge(Redoc::with_url("/docs/redoc", ApiDoc::openapi()));
 	let app = app.merge(router);
@@ -231,24 +259,8 @@ async fn main() {
 	tracing::info!(
 		"SwaggerUI API documentation available at http://{listening_addr}/docs/swagger"
 	);
-	tokio::spawn(
-		axum::Server::bind(&listening_addr)
-			.serve(app.into_make_service_with_connect_info::<SocketAddr>()),
-	);
-
-	// bind the websocket server to :3001
-	let websocket_addr = SocketAddr::from(([0, 0, 0, 0], 3001));
-	let ws_socket = TcpListener::bind(&websocket_addr)
+	axum::Server::bind(&listening_addr)
+		.serve(app.into_make_service_with_connect_info::<SocketAddr>())
 		.await
-		.unwrap_or_else(|err| {
-			panic!("An error occurred when binding websockets to port: {err}")
-		});
-	tracing::debug!("Websockets for frontend listening on ws://{websocket_addr}");
-
-	let peers = PeerMap::new();
-	while let Ok((raw_stream, addr)) = ws_socket.accept().await {
-		let rx = tx.subscribe();
-
-		tokio::spawn(ws::handle_connection(peers.clone(), raw_stream, addr, rx));
-	}
+		.expect("could not start server");
 }
@@ -0,0 +1,319 @@
+//! Enforces the configured [`RetentionPolicy`] by periodically deleting old logs from
+//! the database (and the recent logs cached in the [`Store`]), along with the backtraces
+//! and layers that are no longer used by any log.
+//!
+//! The policy is read from the following environment variables, all of which are
+//! optional:
+//! - `TC_RETENTION_MAX_AGE`: the number of seconds a log is kept for
+//! - `TC_RETENTION_MAX_LOGS_PER_CLIENT`: the number of logs kept for each client
+//! - `TC_RETENTION_MAX_LOGS`: the number of logs kept across the whole server
+//! - `TC_RETENTION_INTERVAL`: the number of seconds between each run, defaults to 300
+//! - `TC_RETENTION_BATCH_SIZE`: the number of rows deleted per query, defaults to 1000
+
+use std::{future::Future, time::Duration};
+
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgQueryResult, PgPool};
+use utoipa::ToSchema;
+
+use crate::{api::Store, prelude::*, utils::env::parse_var};
+
+const DEFAULT_INTERVAL_SECONDS: u64 = 300;
+const DEFAULT_BATCH_SIZE: i64 = 1000;
+
+/// Limits on how many logs are kept, and for how long. A limit that isn't set is not
+/// enforced, so by default logs are kept forever.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct RetentionPolicy {
+	/// Logs older than this many seconds are deleted.
+	#[schema(example = 2_592_000)]
+	pub max_age_seconds: Option<u64>,
+	/// Only this many of the most recent logs are kept for each client.
+	#[schema(example = 100_000)]
+	pub max_logs_per_client: Option<i64>,
+	/// Only this many of the most recent logs are kept across the whole server.
+	#[schema(example = 10_000_000)]
+	pub max_logs: Option<i64>,
+	/// The number of seconds between each time the policy is enforced.
+	#[schema(example = 300)]
+	pub interval_seconds: u64,
+	/// The maximum number of rows deleted by a single query, so that pruning never holds
+	/// locks on large parts of a table for long.
+	#[schema(example = 1000)]
+	pub batch_size: i64,
+}
+
+impl RetentionPolicy {
+	pub fn from_env() -> Self {
+		let max_age_seconds = parse_var("TC_RETENTION_MAX_AGE");
+
+		if let Some(seconds) = max_age_seconds {
+			assert!(
+				max_age(seconds).is_some(),
+				"TC_RETENTION_MAX_AGE is set to '{seconds}', which is too large"
+			);
+		}
+
+		Self {
+			max_age_seconds,
+			max_logs_per_client: parse_var("TC_RETENTION_MAX_LOGS_PER_CLIENT"),
+			max_logs: parse_var("TC_RETENTION_MAX_LOGS"),
+			interval_seconds: parse_var("TC_RETENTION_INTERVAL")
+				.unwrap_or(DEFAULT_INTERVAL_SECONDS)
+				.max(1),
+			batch_size: parse_var("TC_RETENTION_BATCH_SIZE")
+				.unwrap_or(DEFAULT_BATCH_SIZE)
+				.max(1),
+		}
+	}
+
+	/// The date before which logs are too old to be kept, or [`None`] if logs are kept
+	/// regardless of their age (including when no log could be that old).
+	fn cutoff(&self) -> Option<DateTime<Utc>> {
+		Utc::now().checked_sub_signed(max_age(self.max_age_seconds?)?)
+	}
+}
+
+/// `seconds` as a [`chrono::Duration`], if it isn't too large to be one.
+fn max_age(seconds: u64) -> Option<chrono::Duration> {
+	i64::try_from(seconds)
+		.ok()
+		.and_then(chrono::Duration::try_seconds)
+}
+
+/// What was deleted by a single run of the pruning task.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct PruneReport {
+	pub started: DateTime<Utc>,
+	pub finished: DateTime<Utc>,
+	/// Logs deleted for being older than `max_age_seconds`.
+	pub expired_logs: u64,
+	/// Logs deleted for going over `max_logs_per_client`.
+	pub excess_client_logs: u64,
+	/// Logs deleted for going over `max_logs`.
+	pub excess_logs: u64,
+	/// Backtraces deleted because the logs using them were deleted.
+	pub backtraces: u64,
+	/// Layers deleted because no remaining backtrace uses them.
+	pub layers: u64,
+}
+
+/// Calls `delete_batch` until it deletes fewer rows than a full batch, returning the
+/// total number of rows deleted.
+async fn delete_in_batches<F, Fut>(batch_size: i64, mut delete_batch: F) -> Result<u64>
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = std::result::Result<PgQueryResult, sqlx::Error>>,
+{
+	let mut total = 0;
+
+	loop {
+		let deleted = delete_batch().await?.rows_affected();
+
+		total += deleted;
+
+		if deleted < batch_size as u64 {
+			return Ok(total);
+		}
+	}
+}
+
+async fn prune_database(policy: &RetentionPolicy, pool: &PgPool) -> Result<PruneReport> {
+	let started = Utc::now();
+
+	let mut expired_logs = 0;
+	if let Some(cutoff) = policy.cutoff() {
+		expired_logs = delete_in_batches(policy.batch_size, || {
+			sqlx::query!(
+				r###"
+				DELETE FROM "Logs"
+				WHERE id IN (SELECT id FROM "Logs" WHERE date < $1 LIMIT $2)
+				"###,
+				cutoff.naive_utc(),
+				policy.batch_size,
+			)
+			.execute(pool)
+		})
+		.await?;
+	}
+
+	// the newest log to delete for each limit is found once, after which everything
+	// from it back is deleted in batches through the indexes on the date and ID, rather
+	// than ranking every log again for each batch.
+	let mut excess_client_logs = 0;
+	if let Some(max_logs_per_client) = policy.max_logs_per_client {
+		let cutoffs = sqlx::query!(
+			r###"
+			SELECT "Clients".id AS client_id, cutoff.date, cutoff.id
+			FROM "Clients"
+			CROSS JOIN LATERAL (
+				SELECT date, id FROM "Logs"
+				WHERE client_id = "Clients".id
+				ORDER BY date DESC, id DESC
+				OFFSET $1
+				LIMIT 1
+			) AS cutoff
+			"###,
+			max_logs_per_client,
+		)
+		.fetch_all(pool)
+		.await?;
+
+		for cutoff in cutoffs {
+			excess_client_logs += delete_in_batches(policy.batch_size, || {
+				sqlx::query!(
+					r###"
+					DELETE FROM "Logs"
+					WHERE id IN (
+						SELECT id FROM "Logs"
+						WHERE client_id = $1 AND (date, id) <= ($2, $3)
+						LIMIT $4
+					)
+					"###,
+					cutoff.client_id,
+					cutoff.date,
+					cutoff.id,
+					policy.batch_size,
+				)
+				.execute(pool)
+			})
+			.await?;
+		}
+	}
+
+	let mut excess_logs = 0;
+	if let Some(max_logs) = policy.max_logs {
+		let cutoff = sqlx::query!(
+			r###"
+			SELECT date, id FROM "Logs"
+			ORDER BY date DESC, id DESC
+			OFFSET $1
+			LIMIT 1
+			"###,
+			max_logs,
+		)
+		.fetch_optional(pool)
+		.await?;
+
+		if let Some(cutoff) = cutoff {
+			excess_logs = delete_in_batches(policy.batch_size, || {
+				sqlx::query!(
+					r###"
+					DELETE FROM "Logs"
+					WHERE id IN (
+						SELECT id FROM "Logs"
+						WHERE (date, id) <= ($1, $2)
+						LIMIT $3
+					)
+					"###,
+					cutoff.date,
+					cutoff.id,
+					policy.batch_size,
+				)
+				.execute(pool)
+			})
+			.await?;
+		}
+	}
+
+	let (backtraces, layers) = collect_garbage(pool, policy.batch_size).await?;
+
+	Ok(PruneReport {
+		started,
+		finished: Utc::now(),
+		expired_logs,
+		excess_client_logs,
+		excess_logs,
+		backtraces,
+		layers,
+	})
+}
+
+/// Deletes the backtraces that no longer belong to a log, and then the layers that no
+/// longer belong to a backtrace, returning how many of each were deleted.
+pub async fn collect_garbage(pool: &PgPool, batch_size: i64) -> Result<(u64, u64)> {
+	// the join rows and backtraces are deleted in the same statement, as the foreign
+	// keys are only checked once the whole statement has finished.
+	let backtraces = delete_in_batches(batch_size, || {
+		sqlx::query!(
+			r###"
+			WITH orphans AS (
+				SELECT id FROM "Backtraces"
+				WHERE NOT EXISTS (SELECT 1 FROM "Logs" WHERE backtrace_id = "Backtraces".id)
+				LIMIT $1
+			),
+			deleted_layers AS (
+				DELETE FROM "BacktracesLayers" WHERE backtrace_id IN (SELECT id FROM orphans)
+			)
+			DELETE FROM "Backtraces" WHERE id IN (SELECT id FROM orphans)
+			"###,
+			batch_size,
+		)
+		.execute(pool)
+	})
+	.await?;
+
+	let layers = delete_in_batches(batch_size, || {
+		sqlx::query!(
+			r###"
+			DELETE FROM "Layers"
+			WHERE id IN (
+				SELECT id FROM "Layers"
+				WHERE NOT EXISTS (SELECT 1 FROM "BacktracesLayers" WHERE layer_id = "Layers".id)
+				LIMIT $1
+			)
+			"###,
+			batch_size,
+		)
+		.execute(pool)
+	})
+	.await?;
+
+	Ok((backtraces, layers))
+}
+
+/// Applies the age limit of `policy` to the logs kept in memory. The count limits don't
+/// need to be applied, as the capacity of the cache is already lowered to match them.
+fn prune_memory(policy: &RetentionPolicy, store: &Store) {
+	if let Some(cutoff) = policy.cutoff() {
+		store.recent_logs.retain(|log| log.date >= cutoff);
+	}
+}
+
+/// Enforces `policy` every `interval_seconds` for the lifetime of the application,
+/// saving the report of each run into `store` so that it can be retrieved through the
+/// API.
+pub async fn run(policy: RetentionPolicy, pool: PgPool, store: Store) {
+	let mut interval = tokio::time::interval(Duration::from_secs(policy.interval_seconds));
+
+	tracing::info!("Enforcing log retention policy: {policy:?}");
+
+	loop {
+		interval.tick().await;
+
+		prune_memory(&policy, &store);
+
+		match prune_database(&policy, &pool).await {
+			Ok(report) => {
+				let logs = report.expired_logs + report.excess_client_logs + report.excess_logs;
+				let elapsed = (report.finished - report.started).num_milliseconds();
+
+				if logs > 0 || report.backtraces > 0 || report.layers > 0 {
+					tracing::info!(
+						expired_logs = report.expired_logs,
+						excess_client_logs = report.excess_client_logs,
+						excess_logs = report.excess_logs,
+						backtraces = report.backtraces,
+						layers = report.layers,
+						"Pruned {logs} logs in {elapsed}ms",
+					);
+				} else {
+					tracing::debug!("Nothing to prune, took {elapsed}ms");
+				}
+
+				*store.last_prune.lock() = Some(report);
+			}
+			Err(err) => tracing::error!("Could not prune logs: {err}"),
+		}
+	}
+}
@@ -2,9 +2,17 @@ use std::{ops::Deref, sync::Arc};
 
 use parking_lot::Mutex;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct ArcTex<T>(Arc<Mutex<T>>);
 
+// implemented manually, as deriving `Clone` would require `T` to be `Clone` even though
+// only the `Arc` is cloned.
+impl<T> Clone for ArcTex<T> {
+	fn clone(&self) -> Self {
+		Self(self.0.clone())
+	}
+}
+
 impl<T> ArcTex<T> {
 	pub fn new(val: T) -> Self {
 		Self(Arc::new(Mutex::new(val)))
@@ -0,0 +1,23 @@
+use std::{env, fmt::Display, str::FromStr};
+
+/// Reads the environment variable `key` and parses it into `T`, returning [`None`] if it
+/// isn't set or is empty.
+///
+/// # Panics
+///
+/// If the variable is set to something that can't be parsed into `T`, so that mistakes
+/// in the configuration are caught on start-up rather than silently ignored.
+pub fn parse_var<T>(key: &str) -> Option<T>
+where
+	T: FromStr,
+	T::Err: Display,
+{
+	let value = env::var(key)
+		.ok()
+		.filter(|value| !value.trim().is_empty())?;
+
+	match value.trim().parse() {
+		Ok(value) => Some(value),
+		Err(err) => panic!("{key} is set to '{value}', which is invalid: {err}"),
+	}
+}
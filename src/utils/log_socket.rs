@@ -1,9 +1,28 @@
 use tokio::sync::broadcast::{self, Receiver, Sender};
+use uuid::Uuid;
 
 use crate::api::types::Log;
 
-pub type LogSender = Sender<Log>;
-pub type LogReceiver = Receiver<Log>;
+/// Something that happened to the stored logs, which is broadcast to every live
+/// connection.
+#[derive(Debug, Clone)]
+pub enum LogEvent {
+	/// A new log was received.
+	Created(Box<Log>),
+	/// Logs were deleted through the API.
+	Deleted(Vec<DeletedLog>),
+}
+
+/// A log that was deleted, with just enough of it left to tell who could see it.
+#[derive(Debug, Clone, Copy)]
+pub struct DeletedLog {
+	pub id: Uuid,
+	pub client_id: Uuid,
+	pub project_id: Option<Uuid>,
+}
+
+pub type LogSender = Sender<LogEvent>;
+pub type LogReceiver = Receiver<LogEvent>;
 
 pub fn new_log_socket() -> (LogSender, LogReceiver) {
 	let (tx, rx) = broadcast::channel(16);
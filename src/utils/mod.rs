@@ -4,18 +4,27 @@
 //! - Contains a [`W`] type that acts a newtype wrapper for implementing external traits
 //!   on external types (for example the implementations in [`url_try_froms`]).
 //! - An [`ArcTex`] type that is a convenience wrapper over `Arc<Mutex<T>>`
-//! - A [`PeerMap`] type for tracking the connections to the websocket server, using the
-//!   socket address as the key and
+//! - A [`PeerMap`] type for tracking the websockets connected to the server, using the
+//!   socket address as the key and storing what is known about each connection
 //! - A [`Deref`] and [`DerefMut`] implementation for all [`W`]'s
+//! - A [`RecentLogs`] type that caches the most recently received logs in memory
+//! - A [`parse_var`] function for reading optional configuration from the environment
+//! - Functions for generating and hashing the random tokens used for API keys and
+//!   sessions, in [`token`]
 //!
 //! [`ArcTex`]: arctex::ArcTex
 //! [`PeerMap`]: peer_map::PeerMap
+//! [`RecentLogs`]: recent_logs::RecentLogs
+//! [`parse_var`]: env::parse_var
 
 use std::ops::{Deref, DerefMut};
 
 pub mod arctex;
+pub mod env;
 pub mod log_socket;
 pub mod peer_map;
+pub mod recent_logs;
+pub mod token;
 mod url_try_froms;
 pub mod uuid;
 
@@ -1,27 +1,65 @@
-use std::{net::SocketAddr, ops::Deref};
-
-use crate::utils::arctex::ArcTex;
+use std::{net::SocketAddr, sync::Arc};
 
+use chrono::{DateTime, Utc};
 use dashmap::DashMap;
-use futures_channel::mpsc::UnboundedSender;
-use tokio_tungstenite::tungstenite::protocol::Message;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::ws::protocol::Subscription;
 
-type Tx = UnboundedSender<Message>;
-type PeerMapInner = ArcTex<DashMap<SocketAddr, Tx>>;
+/// What is known about a websocket connected to the server.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct PeerInfo {
+	/// The address the websocket connected from.
+	#[schema(value_type = String, example = "127.0.0.1:51234")]
+	pub address: SocketAddr,
+	/// The client the viewer identified itself as when connecting, if any.
+	pub client_id: Option<Uuid>,
+	/// The logs the websocket is subscribed to, or `null` if it isn't subscribed.
+	pub subscription: Option<Subscription>,
+	/// Whether the websocket has paused receiving logs.
+	pub paused: bool,
+	/// The last log the websocket acknowledged receiving.
+	pub last_ack: Option<Uuid>,
+	pub connected_at: DateTime<Utc>,
+}
 
-#[derive(Debug, Clone)]
-pub struct PeerMap(PeerMapInner);
+/// A registry of the websockets currently connected to the server, keyed by the address
+/// they connected from. Peers are added once their connection is established, and must
+/// be removed when it closes.
+#[derive(Debug, Clone, Default)]
+pub struct PeerMap(Arc<DashMap<SocketAddr, PeerInfo>>);
 
 impl PeerMap {
 	pub fn new() -> Self {
-		Self(ArcTex::new(DashMap::new()))
+		Self::default()
 	}
-}
 
-impl Deref for PeerMap {
-	type Target = PeerMapInner;
+	pub fn insert(&self, info: PeerInfo) {
+		self.0.insert(info.address, info);
+	}
+
+	pub fn remove(&self, address: &SocketAddr) {
+		self.0.remove(address);
+	}
+
+	/// Changes what is known about the websocket connected from `address`.
+	pub fn update(&self, address: &SocketAddr, update: impl FnOnce(&mut PeerInfo)) {
+		if let Some(mut info) = self.0.get_mut(address) {
+			update(&mut info);
+		}
+	}
+
+	/// Returns every connected websocket, the longest connected first.
+	pub fn list(&self) -> Vec<PeerInfo> {
+		let mut peers = self
+			.0
+			.iter()
+			.map(|peer| peer.value().clone())
+			.collect::<Vec<_>>();
+
+		peers.sort_by_key(|peer| peer.connected_at);
 
-	fn deref(&self) -> &Self::Target {
-		&self.0
+		peers
 	}
 }
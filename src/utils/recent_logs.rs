@@ -0,0 +1,358 @@
+use std::{
+	cmp::Reverse,
+	collections::{HashMap, VecDeque},
+	sync::Arc,
+};
+
+use uuid::Uuid;
+
+use crate::{
+	api::types::Log,
+	retention::RetentionPolicy,
+	utils::{arctex::ArcTex, env::parse_var},
+};
+
+const DEFAULT_CAPACITY: usize = 10_000;
+const DEFAULT_CAPACITY_PER_CLIENT: usize = 1000;
+
+/// A cached log, along with the order it was received in.
+#[derive(Debug, Clone)]
+struct Entry {
+	seq: u64,
+	log: Arc<Log>,
+}
+
+#[derive(Debug)]
+struct RecentLogsInner {
+	capacity: usize,
+	capacity_per_client: usize,
+	/// The sequence number of the next log that is cached.
+	next_seq: u64,
+	/// Every cached log in the order it was received.
+	logs: VecDeque<Entry>,
+	/// The same logs as `logs`, but grouped by the client that sent them.
+	by_client: HashMap<Uuid, VecDeque<Entry>>,
+}
+
+/// A bounded cache of the most recently received logs, so that recent logs can be
+/// served without querying the database. Once `capacity` logs are cached in total, or
+/// `capacity_per_client` logs are cached for a single client, the oldest logs are
+/// dropped to make room for new ones.
+#[derive(Debug, Clone)]
+pub struct RecentLogs(ArcTex<RecentLogsInner>);
+
+impl RecentLogs {
+	pub fn new(capacity: usize, capacity_per_client: usize) -> Self {
+		Self(ArcTex::new(RecentLogsInner {
+			capacity,
+			capacity_per_client: capacity_per_client.min(capacity),
+			next_seq: 0,
+			logs: VecDeque::with_capacity(capacity),
+			by_client: HashMap::new(),
+		}))
+	}
+
+	/// Creates a cache with the capacities set by the `TC_RECENT_LOGS` and
+	/// `TC_RECENT_LOGS_PER_CLIENT` environment variables, which are lowered to the limits
+	/// of the retention `policy` so that the cache never keeps logs that have been pruned
+	/// from the database.
+	pub fn from_env(policy: &RetentionPolicy) -> Self {
+		let mut capacity = parse_var("TC_RECENT_LOGS").unwrap_or(DEFAULT_CAPACITY);
+		let mut capacity_per_client =
+			parse_var("TC_RECENT_LOGS_PER_CLIENT").unwrap_or(DEFAULT_CAPACITY_PER_CLIENT);
+
+		if let Some(max_logs) = policy.max_logs {
+			capacity = capacity.min(max_logs as usize);
+		}
+
+		if let Some(max_logs_per_client) = policy.max_logs_per_client {
+			capacity_per_client = capacity_per_client.min(max_logs_per_client as usize);
+		}
+
+		Self::new(capacity, capacity_per_client)
+	}
+
+	/// Adds `logs` to the cache, dropping the oldest logs if there isn't enough room for
+	/// them.
+	pub fn extend(&self, logs: impl IntoIterator<Item = Log>) {
+		let mut inner = self.0.lock();
+		let RecentLogsInner {
+			capacity,
+			capacity_per_client,
+			next_seq,
+			logs: all_logs,
+			by_client,
+		} = &mut *inner;
+
+		if *capacity == 0 || *capacity_per_client == 0 {
+			return;
+		}
+
+		for log in logs {
+			let entry = Entry {
+				seq: *next_seq,
+				log: Arc::new(log),
+			};
+			*next_seq += 1;
+
+			// logs dropped for going over either capacity are dropped from both views, so
+			// that they always hold the same logs.
+			let client_logs = by_client.entry(entry.log.client_id).or_default();
+			if client_logs.len() == *capacity_per_client {
+				if let Some(oldest) = client_logs.pop_front() {
+					remove_entry(all_logs, oldest.seq);
+				}
+			}
+
+			client_logs.push_back(entry.clone());
+
+			if all_logs.len() == *capacity {
+				if let Some(oldest) = all_logs.pop_front() {
+					remove_oldest(by_client, &oldest);
+				}
+			}
+
+			all_logs.push_back(entry);
+		}
+	}
+
+	/// Returns up to `limit` of the most recent logs that match `predicate`, newest
+	/// first. If `client_ids` isn't empty, only the logs sent by those clients are
+	/// considered.
+	pub fn latest_matching(
+		&self,
+		client_ids: &[Uuid],
+		limit: usize,
+		mut predicate: impl FnMut(&Log) -> bool,
+	) -> Vec<Log> {
+		let inner = self.0.lock();
+
+		if client_ids.is_empty() {
+			return inner
+				.logs
+				.iter()
+				.rev()
+				.filter(|entry| predicate(&entry.log))
+				.take(limit)
+				.map(|entry| Log::clone(&entry.log))
+				.collect();
+		}
+
+		let mut logs = client_ids
+			.iter()
+			.filter_map(|client_id| inner.by_client.get(client_id))
+			.flat_map(|logs| {
+				logs
+					.iter()
+					.rev()
+					.filter(|entry| predicate(&entry.log))
+					.take(limit)
+					.map(|entry| &entry.log)
+					.collect::<Vec<_>>()
+			})
+			.collect::<Vec<_>>();
+
+		logs.sort_unstable_by_key(|log| Reverse((log.date, log.id)));
+		logs.dedup_by_key(|log| log.id);
+		logs.truncate(limit);
+
+		logs.into_iter().map(|log| Log::clone(log)).collect()
+	}
+
+	/// Drops every cached log that doesn't match `predicate`.
+	pub fn retain(&self, mut predicate: impl FnMut(&Log) -> bool) {
+		let mut inner = self.0.lock();
+
+		inner.logs.retain(|entry| predicate(&entry.log));
+		inner.by_client.retain(|_, logs| {
+			logs.retain(|entry| predicate(&entry.log));
+
+			!logs.is_empty()
+		});
+	}
+}
+
+/// Removes the log numbered `seq` from `logs`, which are in the order they were
+/// received.
+fn remove_entry(logs: &mut VecDeque<Entry>, seq: u64) {
+	if let Ok(index) = logs.binary_search_by_key(&seq, |entry| entry.seq) {
+		logs.remove(index);
+	}
+}
+
+/// Removes `oldest`, the oldest log in the cache, from the logs cached for the client
+/// that sent it.
+fn remove_oldest(by_client: &mut HashMap<Uuid, VecDeque<Entry>>, oldest: &Entry) {
+	let client_id = oldest.log.client_id;
+	let Some(client_logs) = by_client.get_mut(&client_id) else {
+		return;
+	};
+
+	if client_logs
+		.front()
+		.is_some_and(|entry| entry.seq == oldest.seq)
+	{
+		client_logs.pop_front();
+	}
+
+	if client_logs.is_empty() {
+		by_client.remove(&client_id);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+
+	use chrono::{Duration, TimeZone, Utc};
+
+	use super::*;
+	use crate::api::types::Trace;
+
+	/// Creates the `n`th log sent by `client_id`, which is newer than every log before
+	/// it.
+	fn log(client_id: Uuid, n: i64) -> Log {
+		Log {
+			id: Uuid::new_v4(),
+			client_id,
+			project_id: None,
+			message: n.to_string(),
+			message_type: "&str".into(),
+			language: "Rust".into(),
+			snippet: BTreeMap::new(),
+			file_name: "src/main.rs".into(),
+			line_number: 1,
+			backtrace: Trace::default(),
+			warnings: Vec::new(),
+			date: Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap() + Duration::seconds(n),
+			received_from: None,
+		}
+	}
+
+	fn messages(logs: &[Log]) -> Vec<&str> {
+		logs.iter().map(|log| log.message.as_str()).collect()
+	}
+
+	/// Checks that the logs cached in total and for each client are the same.
+	fn assert_consistent(recent_logs: &RecentLogs) {
+		let inner = recent_logs.0.lock();
+		let mut by_client = inner
+			.by_client
+			.values()
+			.flatten()
+			.map(|entry| entry.seq)
+			.collect::<Vec<_>>();
+		by_client.sort_unstable();
+
+		let all = inner.logs.iter().map(|entry| entry.seq).collect::<Vec<_>>();
+
+		assert_eq!(all, by_client);
+	}
+
+	#[test]
+	fn keeps_at_most_capacity_logs() {
+		let recent_logs = RecentLogs::new(3, 3);
+		let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+		recent_logs.extend([log(a, 1), log(b, 2), log(a, 3), log(b, 4), log(a, 5)]);
+
+		assert_eq!(
+			messages(&recent_logs.latest_matching(&[], 10, |_| true)),
+			["5", "4", "3"]
+		);
+		assert_eq!(
+			messages(&recent_logs.latest_matching(&[a], 10, |_| true)),
+			["5", "3"]
+		);
+		assert_consistent(&recent_logs);
+	}
+
+	#[test]
+	fn keeps_at_most_capacity_per_client_logs() {
+		let recent_logs = RecentLogs::new(10, 2);
+		let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+		recent_logs.extend([log(a, 1), log(a, 2), log(b, 3), log(a, 4), log(a, 5)]);
+
+		assert_eq!(
+			messages(&recent_logs.latest_matching(&[a], 10, |_| true)),
+			["5", "4"]
+		);
+		assert_eq!(
+			messages(&recent_logs.latest_matching(&[b], 10, |_| true)),
+			["3"]
+		);
+		assert_consistent(&recent_logs);
+	}
+
+	#[test]
+	fn drops_logs_over_capacity_per_client_from_every_log() {
+		let recent_logs = RecentLogs::new(10, 2);
+		let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+		recent_logs.extend([log(a, 1), log(b, 2), log(a, 3), log(a, 4), log(b, 5)]);
+
+		assert_eq!(
+			messages(&recent_logs.latest_matching(&[], 10, |_| true)),
+			["5", "4", "3", "2"]
+		);
+		assert_consistent(&recent_logs);
+	}
+
+	#[test]
+	fn evicts_mixed_clients_consistently() {
+		let recent_logs = RecentLogs::new(4, 2);
+		let clients = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+
+		recent_logs.extend((0..30).map(|n| log(clients[(n * n % 7 % 3) as usize], n)));
+		assert_consistent(&recent_logs);
+
+		let all = recent_logs.latest_matching(&[], 10, |_| true);
+		let by_client = recent_logs.latest_matching(&clients, 10, |_| true);
+
+		assert_eq!(all.len(), 4);
+		assert_eq!(messages(&all), messages(&by_client));
+	}
+
+	#[test]
+	fn returns_the_newest_matching_logs_of_several_clients() {
+		let recent_logs = RecentLogs::new(10, 10);
+		let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+		recent_logs.extend([log(a, 1), log(b, 2), log(c, 3), log(a, 4), log(b, 5)]);
+
+		assert_eq!(
+			messages(&recent_logs.latest_matching(&[a, b], 3, |_| true)),
+			["5", "4", "2"]
+		);
+		assert_eq!(
+			messages(&recent_logs.latest_matching(&[a, b, c], 10, |log| log.message != "4")),
+			["5", "3", "2", "1"]
+		);
+	}
+
+	#[test]
+	fn retain_drops_logs_from_every_view() {
+		let recent_logs = RecentLogs::new(10, 10);
+		let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+		recent_logs.extend([log(a, 1), log(b, 2), log(a, 3)]);
+		recent_logs.retain(|log| log.client_id != a);
+
+		assert_eq!(
+			messages(&recent_logs.latest_matching(&[], 10, |_| true)),
+			["2"]
+		);
+		assert!(recent_logs.latest_matching(&[a], 10, |_| true).is_empty());
+		assert_consistent(&recent_logs);
+	}
+
+	#[test]
+	fn caches_nothing_without_capacity() {
+		let recent_logs = RecentLogs::new(0, 5);
+
+		recent_logs.extend([log(Uuid::new_v4(), 1)]);
+
+		assert!(recent_logs.latest_matching(&[], 10, |_| true).is_empty());
+	}
+}
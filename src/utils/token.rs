@@ -0,0 +1,18 @@
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+
+/// Generates a random token of `length` letters and digits, such as an API key or a
+/// session token.
+pub fn generate(length: usize) -> String {
+	rand::thread_rng()
+		.sample_iter(&Alphanumeric)
+		.take(length)
+		.map(char::from)
+		.collect()
+}
+
+/// Hashes `token` for storing in, or looking it up from, the database. Tokens are long
+/// and random, so a fast hash is enough here, unlike for passwords.
+pub fn hash(token: &str) -> String {
+	format!("{:x}", Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,565 @@
+pub mod protocol;
+
+use std::{collections::HashSet, net::SocketAddr, time::Duration};
+
+use crate::{
+	api::{
+		extractors::{client::client_exists, user::User},
+		log::{fetch_logs, fetch_logs_after, MAX_REPLAY_SIZE},
+		types::{Cursor, Log, LogPage, Page, SortOrder},
+		Store,
+	},
+	prelude::*,
+	utils::{
+		env::parse_var,
+		log_socket::{DeletedLog, LogEvent, LogReceiver},
+		peer_map::PeerInfo,
+	},
+	ws::protocol::{
+		ClientEnvelope,
+		ClientMessage,
+		ServerEnvelope,
+		ServerMessage,
+		Subscription,
+		CLOSE_IDLE_TIMEOUT,
+		PROTOCOL_VERSION,
+	},
+};
+
+use axum::{
+	extract::{
+		ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+		ConnectInfo,
+		Query,
+		State,
+	},
+	http::StatusCode,
+	response::Response,
+	Extension,
+};
+use chrono::{DateTime, SubsecRound, Utc};
+use futures_util::{
+	stream::{SplitSink, SplitStream},
+	SinkExt,
+	StreamExt,
+};
+use sqlx::PgPool;
+use tokio::{
+	sync::broadcast::error::RecvError,
+	time::{Instant, MissedTickBehavior},
+};
+use uuid::Uuid;
+
+/// The number of recent logs sent to a websocket when it subscribes, before any new
+/// logs.
+const REPLAY_LIMIT: usize = 100;
+/// How long to wait for a websocket to answer a close frame before dropping it.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_PING_INTERVAL_SECONDS: u64 = 30;
+const DEFAULT_IDLE_TIMEOUT_SECONDS: u64 = 90;
+
+/// How often live connections are sent a heartbeat, and how long a websocket can go
+/// without sending anything before it is closed, as set by the `TC_WS_PING_INTERVAL`
+/// and `TC_WS_IDLE_TIMEOUT` environment variables (in seconds). Browsers answer pings
+/// on their own, so only connections that are no longer alive, such as those of a
+/// laptop that went to sleep, time out.
+#[derive(Debug, Clone, Copy)]
+pub struct Heartbeat {
+	pub ping_interval: Duration,
+	pub idle_timeout: Duration,
+}
+
+impl Heartbeat {
+	pub fn from_env() -> Self {
+		let ping_interval = parse_var("TC_WS_PING_INTERVAL")
+			.unwrap_or(DEFAULT_PING_INTERVAL_SECONDS)
+			.max(1);
+		// the timeout has to cover at least one ping and its pong.
+		let idle_timeout = parse_var("TC_WS_IDLE_TIMEOUT")
+			.unwrap_or(DEFAULT_IDLE_TIMEOUT_SECONDS)
+			.max(ping_interval + 1);
+
+		Self {
+			ping_interval: Duration::from_secs(ping_interval),
+			idle_timeout: Duration::from_secs(idle_timeout),
+		}
+	}
+}
+
+/// The state of a single websocket connection, which is driven by the logs being
+/// broadcast and the [`ClientMessage`]s sent by the websocket.
+struct Connection {
+	addr: SocketAddr,
+	store: Store,
+	pool: PgPool,
+	outgoing: SplitSink<WebSocket, Message>,
+	subscription: Option<Subscription>,
+	/// The projects whose logs the user who opened the websocket can read, or [`None`]
+	/// if they can read every log.
+	readable_projects: Option<Vec<Uuid>>,
+	paused: bool,
+	/// The position of the last log sent, so that any logs missed after it can be
+	/// fetched from the database if the websocket falls behind or is paused.
+	cursor: Cursor,
+	/// The broadcast receiver was subscribed before any logs were replayed, so a log can
+	/// be both replayed and received afterwards. The IDs of the replayed logs are kept
+	/// so that those are only sent once.
+	replayed: HashSet<Uuid>,
+}
+
+impl Connection {
+	fn new(
+		addr: SocketAddr,
+		store: Store,
+		pool: PgPool,
+		outgoing: SplitSink<WebSocket, Message>,
+		readable_projects: Option<Vec<Uuid>>,
+		connected_at: DateTime<Utc>,
+	) -> Self {
+		Self {
+			addr,
+			store,
+			pool,
+			outgoing,
+			subscription: None,
+			readable_projects,
+			paused: false,
+			cursor: Cursor {
+				date: connected_at,
+				id: Uuid::nil(),
+			},
+			replayed: HashSet::new(),
+		}
+	}
+
+	async fn send(
+		&mut self,
+		message: ServerMessage,
+	) -> std::result::Result<(), axum::Error> {
+		let json = serde_json::to_string(&ServerEnvelope::from(message))
+			.expect("could not parse message into JSON");
+
+		self.outgoing.send(Message::Text(json)).await
+	}
+
+	async fn send_log(&mut self, log: Log) -> std::result::Result<(), axum::Error> {
+		self.cursor = Cursor::from(&log);
+
+		self.send(ServerMessage::Log { log }).await
+	}
+
+	/// Starts the close handshake by sending `frame`, and waits for the websocket to
+	/// send its own close frame back.
+	async fn close(
+		mut self,
+		frame: CloseFrame<'static>,
+		incoming: &mut SplitStream<WebSocket>,
+	) {
+		tracing::debug!("Closing websocket with {}: {}", self.addr, frame.reason);
+
+		if self
+			.outgoing
+			.send(Message::Close(Some(frame)))
+			.await
+			.is_err()
+		{
+			return;
+		}
+
+		let _ = tokio::time::timeout(CLOSE_TIMEOUT, async {
+			while let Some(Ok(message)) = incoming.next().await {
+				if matches!(message, Message::Close(_)) {
+					break;
+				}
+			}
+		})
+		.await;
+	}
+
+	/// Limits `subscription` to the logs the user can read.
+	fn scope(&self, mut subscription: Subscription) -> Subscription {
+		subscription
+			.filter
+			.project_ids
+			.clone_from(&self.readable_projects);
+
+		subscription
+	}
+
+	fn update_peer(&self) {
+		let subscription = self.subscription.clone();
+		let paused = self.paused;
+
+		self.store.peers.update(&self.addr, |info| {
+			info.subscription = subscription;
+			info.paused = paused;
+		});
+	}
+
+	/// Sends the logs matching the subscription that were sent after the last log this
+	/// websocket received, a page at a time, doing nothing if it isn't subscribed. Once
+	/// [`MAX_REPLAY_SIZE`] logs have been sent, the rest are skipped and the websocket is
+	/// sent a [`ServerMessage::Truncated`] instead.
+	async fn replay_missed(&mut self) -> std::result::Result<(), axum::Error> {
+		let Some(subscription) = self.subscription.clone() else {
+			return Ok(());
+		};
+
+		// the logs still queued in the receiver may be replayed as well.
+		self.replayed.clear();
+
+		loop {
+			let page = fetch_logs_after(
+				&self.pool,
+				&subscription.client_ids,
+				&subscription.filter,
+				self.cursor,
+			)
+			.await;
+
+			let LogPage { logs, next_cursor } = match page {
+				Ok(page) => page,
+				Err(err) => {
+					tracing::error!("Could not fetch missed logs for {}: {err}", self.addr);
+					return Ok(());
+				}
+			};
+
+			self.replayed.extend(logs.iter().map(|log| log.id));
+
+			for log in logs {
+				self.send_log(log).await?;
+			}
+
+			if next_cursor.is_none() {
+				return Ok(());
+			}
+
+			if self.replayed.len() >= MAX_REPLAY_SIZE {
+				return self
+					.send(ServerMessage::Truncated {
+						replayed: self.replayed.len(),
+						cursor: self.cursor,
+					})
+					.await;
+			}
+		}
+	}
+
+	async fn handle_log(&mut self, log: Log) -> std::result::Result<(), axum::Error> {
+		if self.replayed.remove(&log.id) || self.paused {
+			return Ok(());
+		}
+
+		if !self
+			.subscription
+			.as_ref()
+			.is_some_and(|subscription| subscription.matches(&log))
+		{
+			return Ok(());
+		}
+
+		tracing::debug!("Received log, sending to {}", self.addr);
+
+		self.send_log(log).await
+	}
+
+	/// Tells the websocket about the `deleted` logs it may have been sent, even while it
+	/// is paused, so that it stops showing them.
+	async fn handle_deleted(
+		&mut self,
+		deleted: Vec<DeletedLog>,
+	) -> std::result::Result<(), axum::Error> {
+		let Some(subscription) = &self.subscription else {
+			return Ok(());
+		};
+
+		let ids = deleted
+			.iter()
+			.filter(|deleted| subscription.may_have_sent(deleted))
+			.map(|deleted| deleted.id)
+			.collect::<Vec<_>>();
+
+		if ids.is_empty() {
+			return Ok(());
+		}
+
+		self.send(ServerMessage::Deleted { ids }).await
+	}
+
+	async fn handle_lag(&mut self, missed: u64) -> std::result::Result<(), axum::Error> {
+		// the missed logs are replayed anyway when the websocket resumes.
+		if self.paused || self.subscription.is_none() {
+			return Ok(());
+		}
+
+		tracing::warn!(
+			"Websocket with {} fell behind by {missed} logs, replaying them from the database",
+			self.addr
+		);
+
+		self.send(ServerMessage::Lagged { missed }).await?;
+		self.replay_missed().await
+	}
+
+	async fn handle_text(&mut self, text: &str) -> std::result::Result<(), axum::Error> {
+		let envelope = match serde_json::from_str::<ClientEnvelope>(text) {
+			Ok(envelope) => envelope,
+			Err(err) => {
+				return self
+					.send(ServerMessage::Error {
+						message: fmt!("Invalid message: {err}"),
+					})
+					.await
+			}
+		};
+
+		if envelope.version != PROTOCOL_VERSION {
+			return self
+				.send(ServerMessage::Error {
+					message: fmt!(
+						"Unsupported protocol version {}, the server supports version \
+						 {PROTOCOL_VERSION}",
+						envelope.version
+					),
+				})
+				.await;
+		}
+
+		self.handle_message(envelope.message).await
+	}
+
+	async fn handle_message(
+		&mut self,
+		message: ClientMessage,
+	) -> std::result::Result<(), axum::Error> {
+		match message {
+			ClientMessage::Subscribe { subscription } => {
+				let subscription = self.scope(subscription);
+
+				tracing::debug!(
+					"Websocket with {} subscribed to {subscription:?}",
+					self.addr
+				);
+
+				let recent = self.store.recent_logs.latest_matching(
+					&subscription.client_ids,
+					REPLAY_LIMIT,
+					|log| subscription.filter.matches(log),
+				);
+
+				self.subscription = Some(subscription.clone());
+				self.replayed = recent.iter().map(|log| log.id).collect();
+				self.update_peer();
+
+				self
+					.send(ServerMessage::Subscribed { subscription })
+					.await?;
+
+				if !self.paused {
+					for log in recent.into_iter().rev() {
+						self.send_log(log).await?;
+					}
+				}
+			}
+			ClientMessage::Unsubscribe => {
+				self.subscription = None;
+				self.update_peer();
+
+				self.send(ServerMessage::Unsubscribed).await?;
+			}
+			ClientMessage::Pause => {
+				self.paused = true;
+				self.update_peer();
+
+				self.send(ServerMessage::Paused).await?;
+			}
+			ClientMessage::Resume => {
+				let was_paused = self.paused;
+
+				self.paused = false;
+				self.update_peer();
+
+				self.send(ServerMessage::Resumed).await?;
+
+				if was_paused {
+					self.replay_missed().await?;
+				}
+			}
+			ClientMessage::RequestHistory { before, limit } => {
+				let subscription = self
+					.subscription
+					.clone()
+					.unwrap_or_else(|| self.scope(Subscription::default()));
+				let page = Page {
+					limit,
+					cursor: before,
+					order: Some(SortOrder::Desc),
+				};
+
+				let message = match fetch_logs(
+					&self.pool,
+					&subscription.client_ids,
+					&subscription.filter,
+					&page,
+				)
+				.await
+				{
+					Ok(LogPage { logs, next_cursor }) => {
+						ServerMessage::History { logs, next_cursor }
+					}
+					Err(err) => ServerMessage::Error {
+						message: fmt!("Could not fetch history: {err}"),
+					},
+				};
+
+				self.send(message).await?;
+			}
+			ClientMessage::Ack { id } => {
+				self
+					.store
+					.peers
+					.update(&self.addr, |info| info.last_ack = Some(id));
+			}
+			ClientMessage::Ping { nonce } => self.send(ServerMessage::Pong { nonce }).await?,
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+pub struct ConnectQuery {
+	/// The client the viewer is registered as, since browsers can't send the `client-id`
+	/// header when opening a websocket.
+	client_id: Option<Uuid>,
+}
+
+/// Upgrades the request to a websocket, which is then controlled through the messages in
+/// [`protocol`]. Only logs in the projects the user belongs to are sent through it.
+#[axum_macros::debug_handler]
+pub async fn websocket(
+	user: User,
+	ws: WebSocketUpgrade,
+	ConnectInfo(addr): ConnectInfo<SocketAddr>,
+	State(store): State<Store>,
+	Extension(pool): Extension<PgPool>,
+	Query(query): Query<ConnectQuery>,
+) -> Result<Response> {
+	if let Some(client_id) = query.client_id {
+		if !client_exists(&pool, client_id).await? {
+			return Err(Error::ResponseError(
+				StatusCode::BAD_REQUEST,
+				fmt!("Client with ID {client_id} does not exist"),
+			));
+		}
+	}
+
+	// the receiver is subscribed before the upgrade, so that no logs are missed between
+	// the recent logs being replayed and the connection being established.
+	let log_receiver = store.sender.subscribe();
+	let info = PeerInfo {
+		address: addr,
+		client_id: query.client_id,
+		subscription: None,
+		paused: false,
+		last_ack: None,
+		connected_at: Utc::now().trunc_subsecs(6),
+	};
+
+	Ok(ws.on_upgrade(move |socket| {
+		handle_connection(
+			socket,
+			info,
+			user.readable_projects(),
+			store,
+			pool,
+			log_receiver,
+		)
+	}))
+}
+
+async fn handle_connection(
+	socket: WebSocket,
+	info: PeerInfo,
+	readable_projects: Option<Vec<Uuid>>,
+	store: Store,
+	pool: PgPool,
+	mut log_receiver: LogReceiver,
+) {
+	let addr = info.address;
+	let connected_at = info.connected_at;
+
+	tracing::debug!("Websocket connection established with {addr}");
+
+	store.peers.insert(info);
+
+	let (outgoing, mut incoming) = socket.split();
+	let mut connection = Connection::new(
+		addr,
+		store.clone(),
+		pool,
+		outgoing,
+		readable_projects,
+		connected_at,
+	);
+
+	let heartbeat = store.heartbeat;
+	let mut ping = tokio::time::interval_at(
+		Instant::now() + heartbeat.ping_interval,
+		heartbeat.ping_interval,
+	);
+	ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+	// reset whenever anything is received, including the pongs answering the pings.
+	let idle = tokio::time::sleep(heartbeat.idle_timeout);
+	tokio::pin!(idle);
+
+	let close_frame = loop {
+		let result = tokio::select! {
+			received = log_receiver.recv() => match received {
+				Ok(LogEvent::Created(log)) => connection.handle_log(*log).await,
+				Ok(LogEvent::Deleted(deleted)) => connection.handle_deleted(deleted).await,
+				Err(RecvError::Lagged(missed)) => connection.handle_lag(missed).await,
+				Err(RecvError::Closed) => break Some(CloseFrame {
+					code: close_code::AWAY,
+					reason: "The server is shutting down".into(),
+				}),
+			},
+			message = incoming.next() => {
+				idle.as_mut().reset(Instant::now() + heartbeat.idle_timeout);
+
+				match message {
+					Some(Ok(Message::Text(text))) => connection.handle_text(&text).await,
+					// the reply to a close frame is queued automatically, and sent once the
+					// connection is closed below.
+					None | Some(Err(_) | Ok(Message::Close(_))) => break None,
+					Some(Ok(_)) => Ok(()),
+				}
+			}
+			_ = ping.tick() => connection.outgoing.send(Message::Ping(Vec::new())).await,
+			() = &mut idle => break Some(CloseFrame {
+				code: CLOSE_IDLE_TIMEOUT,
+				reason: fmt!(
+					"Nothing was received for {} seconds",
+					heartbeat.idle_timeout.as_secs()
+				)
+				.into(),
+			}),
+		};
+
+		if let Err(err) = result {
+			tracing::error!("Could not send message to front-end: {err}");
+			break None;
+		}
+	};
+
+	match close_frame {
+		Some(frame) => connection.close(frame, &mut incoming).await,
+		None => {
+			let _ = connection.outgoing.close().await;
+		}
+	}
+
+	tracing::debug!("Websocket connection with {addr} closed");
+	store.peers.remove(&addr);
+}
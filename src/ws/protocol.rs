@@ -0,0 +1,168 @@
+//! The messages sent over the websocket at `/ws`, as JSON text frames.
+//!
+//! Every message is wrapped in an envelope carrying the [`PROTOCOL_VERSION`] it was
+//! written for, with the kind of message in its `type` field, for example:
+//!
+//! ```json
+//! { "version": 1, "type": "subscribe", "subscription": { "client_ids": [] } }
+//! ```
+//!
+//! A websocket isn't sent any logs until it has sent a [`ClientMessage::Subscribe`].
+
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+	api::types::{Cursor, Log, LogFilter},
+	utils::log_socket::DeletedLog,
+};
+
+/// The version of the protocol implemented by the server. Messages for any other
+/// version are rejected with a [`ServerMessage::Error`].
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The close code sent when a websocket hasn't sent anything, not even a pong, for
+/// longer than the idle timeout of the server.
+pub const CLOSE_IDLE_TIMEOUT: u16 = 4000;
+
+/// Decides which logs are sent to a websocket.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct Subscription {
+	/// Only logs sent by these clients are sent to the websocket. If empty, logs from
+	/// every client are sent.
+	#[serde(default)]
+	pub client_ids: Vec<Uuid>,
+	#[serde(flatten)]
+	pub filter: LogFilter,
+}
+
+impl Subscription {
+	pub fn matches(&self, log: &Log) -> bool {
+		(self.client_ids.is_empty() || self.client_ids.contains(&log.client_id))
+			&& self.filter.matches(log)
+	}
+
+	/// Whether the websocket could have been sent the `deleted` log. Only the client and
+	/// project of the log are left to check, so this may include logs that didn't match
+	/// the rest of the filter.
+	pub fn may_have_sent(&self, deleted: &DeletedLog) -> bool {
+		let in_project = |project_id: Uuid| deleted.project_id == Some(project_id);
+
+		(self.client_ids.is_empty() || self.client_ids.contains(&deleted.client_id))
+			&& self.filter.project_id.is_none_or(in_project)
+			&& self
+				.filter
+				.project_ids
+				.as_ref()
+				.is_none_or(|project_ids| project_ids.iter().copied().any(in_project))
+	}
+}
+
+/// A message sent by a websocket to the server.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+	/// Start receiving the logs matching `subscription`, replacing any previous
+	/// subscription. The most recent matching logs are sent straight away.
+	Subscribe { subscription: Subscription },
+	/// Stop receiving logs, without closing the websocket.
+	Unsubscribe,
+	/// Stop receiving logs until [`ClientMessage::Resume`] is sent.
+	Pause,
+	/// Start receiving logs again after [`ClientMessage::Pause`], starting with the logs
+	/// that were sent while paused.
+	Resume,
+	/// Request a page of older logs matching the current subscription, newest first.
+	RequestHistory {
+		/// Only return logs that come before this cursor, as returned in `next_cursor` of
+		/// the previous [`ServerMessage::History`].
+		#[schema(value_type = Option<String>)]
+		before: Option<Cursor>,
+		/// The maximum number of logs to return, defaults to 100 and cannot be more than
+		/// 1000.
+		limit: Option<i64>,
+	},
+	/// Acknowledge that the log with the given `id` has been received, which is shown as
+	/// the `last_ack` of the connection in `GET /api/connections`.
+	Ack { id: Uuid },
+	/// Check that the connection is alive, which is answered with a
+	/// [`ServerMessage::Pong`] carrying the same `nonce`.
+	Ping { nonce: Option<u64> },
+}
+
+/// A message sent by the server to a websocket.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+	/// A log matching the websocket's subscription.
+	Log {
+		log: Log,
+	},
+	Subscribed {
+		subscription: Subscription,
+	},
+	Unsubscribed,
+	Paused,
+	/// Sent after [`ClientMessage::Resume`], before the logs that were missed while
+	/// paused.
+	Resumed,
+	/// The logs with these IDs were deleted, and should no longer be shown.
+	Deleted {
+		ids: Vec<Uuid>,
+	},
+	/// The answer to [`ClientMessage::RequestHistory`].
+	History {
+		logs: Vec<Log>,
+		/// Passed as `before` to request the next page, or `null` if this is the last
+		/// page.
+		next_cursor: Option<String>,
+	},
+	/// The websocket fell behind by `missed` logs, so the logs matching its subscription
+	/// that were sent since the last log it received are sent again, oldest first.
+	Lagged {
+		missed: u64,
+	},
+	/// Replaying the logs missed while lagging or paused stopped after `replayed` logs,
+	/// so the logs sent after `cursor` up until the next live log have to be fetched
+	/// from `GET /api/logs` with `order=asc`.
+	Truncated {
+		replayed: usize,
+		#[schema(value_type = String)]
+		cursor: Cursor,
+	},
+	/// The answer to [`ClientMessage::Ping`].
+	Pong {
+		nonce: Option<u64>,
+	},
+	/// A message sent by the websocket couldn't be handled.
+	Error {
+		message: String,
+	},
+}
+
+/// A [`ClientMessage`], along with the version of the protocol it was written for.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct ClientEnvelope {
+	#[schema(example = 1)]
+	pub version: u32,
+	#[serde(flatten)]
+	pub message: ClientMessage,
+}
+
+/// A [`ServerMessage`], along with the version of the protocol it was written for.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, ToSchema)]
+pub struct ServerEnvelope {
+	#[schema(example = 1)]
+	pub version: u32,
+	#[serde(flatten)]
+	pub message: ServerMessage,
+}
+
+impl From<ServerMessage> for ServerEnvelope {
+	fn from(message: ServerMessage) -> Self {
+		Self {
+			version: PROTOCOL_VERSION,
+			message,
+		}
+	}
+}
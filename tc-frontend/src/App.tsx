@@ -2,6 +2,7 @@ import "./App.css";
 
 import { HeaderBar } from "@/components/header-bar";
 import { ThemeProvider } from "@/components/theme-provider";
+import { AuthProvider } from "@/components/auth-provider";
 import { Toaster } from "@/components/ui/toaster";
 import { SettingsProvider } from "@/components/settings-provider";
 import { LogsProvider } from "@/components/logs-provider";
@@ -11,13 +12,15 @@ function App() {
 	return (
 		<ThemeProvider defaultTheme="system" storageKey="vite-ui-theme">
 			<SettingsProvider>
-				<LogsProvider>
-					<>
-						<HeaderBar />
-						<LogsArea />
-						<Toaster />
-					</>
-				</LogsProvider>
+				<AuthProvider>
+					<LogsProvider>
+						<>
+							<HeaderBar />
+							<LogsArea />
+							<Toaster />
+						</>
+					</LogsProvider>
+				</AuthProvider>
 			</SettingsProvider>
 		</ThemeProvider>
 	);
@@ -0,0 +1,142 @@
+/* eslint-disable react-refresh/only-export-components */
+import React, { createContext, useContext, useEffect, useState } from "react";
+import { Button } from "@/components/ui/button";
+import {
+	Card,
+	CardContent,
+	CardDescription,
+	CardFooter,
+	CardHeader,
+	CardTitle,
+} from "@/components/ui/card";
+import { Input } from "@/components/ui/input";
+import { Label } from "@/components/ui/label";
+
+export type Role = "read_only" | "member" | "admin";
+
+export type User = {
+	id: string;
+	username: string;
+	is_admin: boolean;
+	memberships: Array<{ project_id: string; role: Role }>;
+};
+
+type AuthProviderProps = {
+	children: React.ReactNode;
+};
+
+type AuthProviderState = {
+	user?: User;
+	logout: () => void;
+};
+
+const initialState: AuthProviderState = {
+	user: undefined,
+	logout: () => null,
+};
+
+const AuthProviderContext = createContext<AuthProviderState>(initialState);
+
+function LoginForm({ onLogin }: { onLogin: (user: User) => void }) {
+	const [username, setUsername] = useState("");
+	const [password, setPassword] = useState("");
+	const [error, setError] = useState<string>();
+
+	const submit = async (event: React.FormEvent) => {
+		event.preventDefault();
+
+		const res = await fetch(`/api/auth/login`, {
+			method: "POST",
+			headers: { "content-type": "application/json" },
+			body: JSON.stringify({ username, password }),
+		});
+
+		if (!res.ok) {
+			setError(await res.text());
+			return;
+		}
+
+		onLogin((await res.json()) as User);
+	};
+
+	return (
+		<div className="flex h-screen items-center justify-center">
+			<Card className="w-96">
+				<form onSubmit={submit}>
+					<CardHeader>
+						<CardTitle>TraceCTRL</CardTitle>
+						<CardDescription>Log in to see the logs of your projects.</CardDescription>
+					</CardHeader>
+					<CardContent className="space-y-4">
+						<div className="space-y-2">
+							<Label htmlFor="username">Username</Label>
+							<Input
+								id="username"
+								autoComplete="username"
+								value={username}
+								onChange={(event) => setUsername(event.target.value)}
+							/>
+						</div>
+						<div className="space-y-2">
+							<Label htmlFor="password">Password</Label>
+							<Input
+								id="password"
+								type="password"
+								autoComplete="current-password"
+								value={password}
+								onChange={(event) => setPassword(event.target.value)}
+							/>
+						</div>
+						{error && <p className="text-sm text-destructive">{error}</p>}
+					</CardContent>
+					<CardFooter>
+						<Button type="submit" className="w-full">
+							Log in
+						</Button>
+					</CardFooter>
+				</form>
+			</Card>
+		</div>
+	);
+}
+
+// only renders its children once a user is logged in, showing a login form until then.
+export function AuthProvider({ children, ...props }: AuthProviderProps) {
+	const [user, setUser] = useState<User>();
+	const [loading, setLoading] = useState(true);
+
+	useEffect(() => {
+		fetch(`/api/auth/me`)
+			.then(async (res) => {
+				if (res.ok) {
+					setUser((await res.json()) as User);
+				}
+			})
+			.finally(() => setLoading(false));
+	}, []);
+
+	const logout = () => {
+		fetch(`/api/auth/logout`, { method: "POST" }).finally(() =>
+			setUser(undefined),
+		);
+	};
+
+	if (loading) {
+		return null;
+	}
+
+	return (
+		<AuthProviderContext.Provider {...props} value={{ user, logout }}>
+			{user ? children : <LoginForm onLogin={setUser} />}
+		</AuthProviderContext.Provider>
+	);
+}
+
+export function useAuth() {
+	const context = useContext(AuthProviderContext);
+
+	if (context === undefined)
+		throw new Error("useAuth must be used within an AuthProvider");
+
+	return context;
+}
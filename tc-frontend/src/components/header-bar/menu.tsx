@@ -28,10 +28,12 @@ import {
 	settingsFormSchema,
 	useSettings,
 } from "@/components/settings-provider";
+import { useAuth } from "@/components/auth-provider";
 
 export function HeaderBarMenu() {
 	const { toast } = useToast();
 	const { settings, setSettings } = useSettings();
+	const { user, logout } = useAuth();
 
 	const settingsForm = useForm<z.infer<typeof settingsFormSchema>>({
 		resolver: zodResolver(settingsFormSchema),
@@ -94,6 +96,14 @@ export function HeaderBarMenu() {
 						<Button type="submit">Save changes</Button>
 					</form>
 				</Form>
+				<div className={cn("flex", "items-center", "justify-between", "mt-8")}>
+					<span className={cn("text-sm")}>
+						Logged in as <span className={cn("font-mono")}>{user?.username}</span>
+					</span>
+					<Button variant="outline" onClick={logout}>
+						Log out
+					</Button>
+				</div>
 			</SheetContent>
 		</Sheet>
 	);
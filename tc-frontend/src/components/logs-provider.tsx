@@ -1,6 +1,7 @@
 /* eslint-disable react-refresh/only-export-components */
 import React, { createContext, useContext, useState } from "react";
 import { Log } from "@/lib/log";
+import { ServerEnvelope, encode } from "@/lib/protocol";
 import { useSettings } from "./settings-provider";
 import useWebSocket from "react-use-websocket";
 
@@ -8,12 +9,10 @@ const logsKey = "tracectrl-logs" as const;
 
 type ClientSettings = {
 	logs: Array<Log>;
-	clientId?: number;
 };
 
 const _default: ClientSettings = {
 	logs: [],
-	clientId: undefined,
 };
 
 type LogsProviderProps = {
@@ -44,60 +43,79 @@ export function LogsProvider({
 			) as ClientSettings) || defaultSettings,
 	);
 
-	const { logs, clientId } = clientSettings;
+	const { logs } = clientSettings;
 
+	const fetchLogs = () => {
+		fetch(`/api/logs`).then(async (res) => {
+			try {
+				const json = await res.json();
+				const response = json as { logs: Array<Log> };
+				const _new = { logs: response.logs };
+
+				setLogs(_new);
+				localStorage.setItem(logsKey, JSON.stringify(_new));
+			} catch (error) {
+				console.error(`could not convert response: ${error}`);
+			}
+		});
+	};
+
+	// the session cookie is sent when the websocket is opened, so only the logs of the
+	// projects the user belongs to are received.
 	useWebSocket(`ws://${settings.websocketHost}`, {
 		reconnectAttempts: 5,
-		onOpen: () => {
+		onOpen: (event) => {
 			console.log("connection established");
 
-			if (clientId) {
-				fetch(`/api/logs`, {
-					method: "GET",
-					headers: {
-						"client-id": `${clientId}`,
-					},
-				}).then(async (res) => {
-					try {
-						const json = await res.json();
-						console.log(json);
-						const response = json as Array<Log>;
-						const _new = { logs: response, clientId: clientId };
-
-						setLogs(_new);
-						localStorage.setItem(logsKey, JSON.stringify(_new));
-					} catch (error) {
-						console.error(`could not convert response: ${error}`);
-					}
-				});
+			// the server only sends logs once it knows which clients they should be from,
+			// and an empty list subscribes to every client.
+			const socket = event.target as WebSocket;
+			socket.send(
+				encode({
+					type: "subscribe",
+					subscription: { client_ids: [] },
+				}),
+			);
+
+			fetchLogs();
+		},
+		onMessage: (event) => {
+			const message = JSON.parse(event.data) as ServerEnvelope;
 
+			if (message.type === "lagged" || message.type === "error") {
+				console.warn(`websocket message: ${event.data}`);
 				return;
 			}
 
-			type Response = {
-				client_id: number;
-			};
-
-			fetch(`/api/get_or_register_client`, { method: "post" }).then(
-				async (res) => {
-					try {
-						const response = (await res.json()) as Response;
-						const _new = { logs: logs, clientId: response.client_id };
-						setLogs(_new);
-						localStorage.setItem(logsKey, JSON.stringify(_new));
-					} catch (error) {
-						console.error(`could not convert response: ${error}`);
-					}
-				},
-			);
-		},
-		onMessage: (event) => {
-			const log = JSON.parse(event.data) as Log;
+			// too many logs were missed to replay them all, so the latest are fetched
+			// again instead.
+			if (message.type === "truncated") {
+				console.warn(`websocket message: ${event.data}`);
+				fetchLogs();
+				return;
+			}
+
+			if (message.type === "deleted") {
+				const _new = {
+					logs: logs.filter((log) => !message.ids.includes(log.id)),
+				};
+				setLogs(_new);
+				localStorage.setItem(logsKey, JSON.stringify(_new));
+				return;
+			}
+
+			if (message.type !== "log") {
+				return;
+			}
+
+			const log = message.log;
 
-			if (log) {
+			// recent logs are replayed when the websocket connects, so they may have
+			// already been fetched.
+			if (log && !logs.some((existing) => existing.id === log.id)) {
 				console.log(`log recieved: ${log.id}`);
 
-				const _new = { logs: [...logs, log], clientId };
+				const _new = { logs: [...logs, log] };
 				setLogs(_new);
 				localStorage.setItem(logsKey, JSON.stringify(_new));
 			}
@@ -9,7 +9,7 @@ type Settings = {
 };
 
 export const _defaultSettings: Settings = {
-	websocketHost: "localhost:3001",
+	websocketHost: "localhost:3000/ws",
 };
 
 export const settingsFormSchema = z.object({
@@ -10,6 +10,8 @@ type Layer = {
 
 export type Log = {
 	id: typeof uuidv4;
+	client_id: string;
+	project_id: string | null;
 	backtrace: { layers: Array<Layer> };
 	date: Date;
 	file_name: string;
@@ -0,0 +1,46 @@
+import { Log } from "@/lib/log";
+
+// mirrors the messages in `src/ws/protocol.rs`, which are also exported in the
+// OpenAPI documentation.
+export const PROTOCOL_VERSION = 1 as const;
+
+export type Subscription = {
+	client_ids: Array<string>;
+	project_id?: string;
+	from?: string;
+	to?: string;
+	language?: string;
+	message_type?: string;
+	file_name?: string;
+	sender?: string;
+	has_warnings?: boolean;
+	message?: string;
+};
+
+export type ClientMessage =
+	| { type: "subscribe"; subscription: Subscription }
+	| { type: "unsubscribe" }
+	| { type: "pause" }
+	| { type: "resume" }
+	| { type: "request_history"; before?: string; limit?: number }
+	| { type: "ack"; id: string }
+	| { type: "ping"; nonce?: number };
+
+export type ServerMessage =
+	| { type: "log"; log: Log }
+	| { type: "subscribed"; subscription: Subscription }
+	| { type: "unsubscribed" }
+	| { type: "paused" }
+	| { type: "resumed" }
+	| { type: "deleted"; ids: Array<string> }
+	| { type: "history"; logs: Array<Log>; next_cursor: string | null }
+	| { type: "lagged"; missed: number }
+	| { type: "truncated"; replayed: number; cursor: string }
+	| { type: "pong"; nonce: number | null }
+	| { type: "error"; message: string };
+
+export type ServerEnvelope = { version: number } & ServerMessage;
+
+export function encode(message: ClientMessage): string {
+	return JSON.stringify({ version: PROTOCOL_VERSION, ...message });
+}
@@ -0,0 +1,68 @@
+//! Procedural macros used by the `log!` macro of `tracectrl-client`, which aren't part
+//! of its public API.
+
+#![warn(clippy::pedantic)]
+
+use proc_macro::{Delimiter, Group, Punct, Spacing, TokenStream, TokenTree};
+
+/// Turns a literal into the message it is logged as. String literals are used as
+/// format strings, so that the variables they name are captured, and other literals
+/// are sent as their `Display` output.
+///
+/// The literal is passed on with its own span, so that the variables named by a format
+/// string are looked up where the literal was written rather than in `log!`.
+///
+/// # Panics
+///
+/// Never, as the path of the macro or function the literal is passed to is always
+/// valid.
+#[proc_macro]
+pub fn literal_message(input: TokenStream) -> TokenStream {
+	let literal = unwrap_group(input);
+
+	let (path, argument) = if is_string(&literal) {
+		("::std::format!", literal)
+	} else {
+		let mut reference =
+			TokenStream::from(TokenTree::Punct(Punct::new('&', Spacing::Alone)));
+		reference.extend(literal);
+
+		("::std::string::ToString::to_string", reference)
+	};
+
+	let mut output: TokenStream = path.parse().expect("the path is valid");
+	output.extend([TokenTree::Group(Group::new(
+		Delimiter::Parenthesis,
+		argument,
+	))]);
+
+	output
+}
+
+/// Removes the invisible group that `macro_rules!` wraps some of the fragments it passes
+/// on in.
+fn unwrap_group(input: TokenStream) -> TokenStream {
+	let mut tokens = input.clone().into_iter();
+
+	match (tokens.next(), tokens.next()) {
+		(Some(TokenTree::Group(group)), None) if group.delimiter() == Delimiter::None => {
+			unwrap_group(group.stream())
+		}
+		_ => input,
+	}
+}
+
+/// Whether `literal` is a string literal, which may be raw, as opposed to any other
+/// literal such as a number, a character or a byte string.
+fn is_string(literal: &TokenStream) -> bool {
+	let mut tokens = literal.clone().into_iter();
+
+	match (tokens.next(), tokens.next()) {
+		(Some(TokenTree::Literal(literal)), None) => {
+			let literal = literal.to_string();
+
+			literal.starts_with('"') || literal.starts_with("r\"") || literal.starts_with("r#")
+		}
+		_ => false,
+	}
+}
@@ -0,0 +1,119 @@
+//! Caches the ID and API key of a client between runs, in a file that only the user
+//! running the program can read.
+
+use std::{
+	fs,
+	io::{self, Write},
+	path::{Path, PathBuf},
+};
+
+use uuid::Uuid;
+
+use crate::error::Error;
+
+/// The ID and API key the client sends logs with, which are cached between runs so
+/// that the same program always shows up as the same client.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub(crate) struct Credentials {
+	pub server: String,
+	pub client_id: Uuid,
+	pub api_key: String,
+}
+
+/// The file the credentials of the running program are cached in, unless another is
+/// set with [`ClientBuilder::cache_path`](crate::ClientBuilder::cache_path).
+pub(crate) fn default_path() -> PathBuf {
+	let program = std::env::current_exe()
+		.ok()
+		.and_then(|exe| {
+			exe
+				.file_stem()
+				.map(|stem| stem.to_string_lossy().into_owned())
+		})
+		.unwrap_or_else(|| "program".into());
+
+	let dir = std::env::var_os("XDG_CACHE_HOME")
+		.map(PathBuf::from)
+		.filter(|dir| dir.is_absolute())
+		.or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+		.or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+		.unwrap_or_else(std::env::temp_dir);
+
+	dir.join("tracectrl-client").join(format!("{program}.json"))
+}
+
+/// Checks that the file at `path` is a regular file owned by the current user, that
+/// no one else can write to, so that another user can't make the program send its
+/// logs somewhere else, or read them.
+#[cfg(unix)]
+fn check_owner(path: &Path, metadata: &fs::Metadata) -> Result<(), Error> {
+	use std::os::unix::fs::MetadataExt;
+
+	// SAFETY: `geteuid` has no preconditions and can't fail.
+	let user = unsafe { libc::geteuid() };
+
+	if metadata.is_file() && metadata.uid() == user && metadata.mode() & 0o022 == 0 {
+		Ok(())
+	} else {
+		Err(Error::UntrustedCache(path.to_owned()))
+	}
+}
+
+#[cfg(not(unix))]
+fn check_owner(path: &Path, metadata: &fs::Metadata) -> Result<(), Error> {
+	if metadata.is_file() {
+		Ok(())
+	} else {
+		Err(Error::UntrustedCache(path.to_owned()))
+	}
+}
+
+/// Reads the credentials cached at `path`, returning [`None`] if there aren't any.
+pub(crate) fn read(path: &Path) -> Result<Option<Credentials>, Error> {
+	let metadata = match fs::symlink_metadata(path) {
+		Ok(metadata) => metadata,
+		Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+		Err(err) => return Err(err.into()),
+	};
+
+	check_owner(path, &metadata)?;
+
+	Ok(serde_json::from_slice(&fs::read(path)?).ok())
+}
+
+/// Caches `credentials` at `path`, in a file that only the current user can read,
+/// creating the directories it is in if needed.
+pub(crate) fn write(path: &Path, credentials: &Credentials) -> Result<(), Error> {
+	let cache = serde_json::to_vec(credentials)?;
+
+	if let Some(dir) = path.parent() {
+		let mut builder = fs::DirBuilder::new();
+		builder.recursive(true);
+
+		#[cfg(unix)]
+		std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+		builder.create(dir)?;
+	}
+
+	// the old cache is replaced rather than written over, so that the new file is
+	// created with the right permissions, and another user can't create it first.
+	match fs::symlink_metadata(path) {
+		Ok(metadata) => {
+			check_owner(path, &metadata)?;
+			fs::remove_file(path)?;
+		}
+		Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+		Err(err) => return Err(err.into()),
+	}
+
+	let mut options = fs::OpenOptions::new();
+	options.write(true).create_new(true);
+
+	#[cfg(unix)]
+	std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+	options.open(path)?.write_all(&cache)?;
+
+	Ok(())
+}
@@ -0,0 +1,311 @@
+//! Captures everything a [`LogBody`] needs from the place it was logged: the
+//! backtrace, and the source around the log.
+
+use std::{
+	backtrace::{Backtrace, BacktraceStatus},
+	collections::{BTreeMap, HashMap},
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex, OnceLock, PoisonError},
+};
+
+use crate::types::{Layer, LogBody, Trace};
+
+/// The number of lines of source captured either side of a log.
+const SNIPPET_CONTEXT: u32 = 5;
+
+/// The functions whose frames are left out of backtraces, as they belong to the
+/// standard library, the runtime or this crate rather than the program being logged.
+const IGNORED_FRAMES: &[&str] = &[
+	"std::",
+	"core::",
+	"alloc::",
+	"<std::",
+	"<core::",
+	"<alloc::",
+	"tracectrl_client::",
+	"<tracectrl_client::",
+	"__rust",
+	"rust_begin_unwind",
+	"__libc_start",
+	"_start",
+];
+
+/// Where a log was made, as given by the [`log!`](crate::log) macro.
+#[derive(Debug, Clone, Copy)]
+pub struct Location<'a> {
+	pub file: &'a str,
+	pub line: u32,
+	/// The `CARGO_MANIFEST_DIR` of the crate that made the log, which `file` may be
+	/// relative to.
+	pub manifest_dir: &'a str,
+}
+
+/// The lines of every source file looked up so far, or [`None`] if it couldn't be found
+/// or read, so that files are only searched for and read once however often they are
+/// logged from. Files are keyed by the path and manifest directory they were looked up
+/// with.
+type SourceCache = Mutex<HashMap<(String, String), Option<Arc<Vec<String>>>>>;
+
+fn source_cache() -> &'static SourceCache {
+	static CACHE: OnceLock<SourceCache> = OnceLock::new();
+
+	CACHE.get_or_init(Mutex::default)
+}
+
+/// Finds the source file at `path`, which is relative to the workspace the program was
+/// built in unless it is absolute. That workspace is one of the ancestors of
+/// `manifest_dir`, or the current directory when running through `cargo run`.
+fn find_source(path: &str, manifest_dir: &str) -> Option<PathBuf> {
+	let path = Path::new(path.strip_prefix("./").unwrap_or(path));
+
+	if path.is_absolute() {
+		return path.is_file().then(|| path.to_owned());
+	}
+
+	Path::new(manifest_dir)
+		.ancestors()
+		.map(Path::to_owned)
+		.chain(std::env::current_dir().ok())
+		.map(|dir| dir.join(path))
+		.find(|candidate| candidate.is_file())
+}
+
+/// Returns the lines of the source file at `path`, if it can be found.
+fn read_source(path: &str, manifest_dir: &str) -> Option<Arc<Vec<String>>> {
+	let key = (path.to_owned(), manifest_dir.to_owned());
+
+	if let Some(source) = source_cache()
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.get(&key)
+	{
+		return source.clone();
+	}
+
+	// the file is read without holding the lock, so that logging from other threads
+	// doesn't wait on the disk. Threads that miss at the same time may both read it,
+	// but only the first to finish is cached.
+	let source = find_source(path, manifest_dir)
+		.and_then(|path| std::fs::read_to_string(path).ok())
+		.map(|source| Arc::new(source.lines().map(str::to_owned).collect()));
+
+	source_cache()
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.entry(key)
+		.or_insert(source)
+		.clone()
+}
+
+/// A frame of a [`Backtrace`], as parsed from its [`Display`](std::fmt::Display)
+/// output.
+#[derive(Debug, PartialEq, Eq)]
+struct Frame {
+	name: String,
+	location: Option<(String, u32, u32)>,
+}
+
+/// Parses the frames out of the output of a resolved backtrace, which are written as:
+///
+/// ```text
+///    0: my_crate::my_function
+///              at ./src/main.rs:10:5
+/// ```
+fn parse_frames(backtrace: &str) -> Vec<Frame> {
+	let mut frames: Vec<Frame> = Vec::new();
+
+	for line in backtrace.lines().map(str::trim) {
+		if let Some(location) = line.strip_prefix("at ") {
+			let mut parts = location.rsplitn(3, ':');
+			let (Some(column), Some(line), Some(file)) =
+				(parts.next(), parts.next(), parts.next())
+			else {
+				continue;
+			};
+
+			if let (Some(frame), Ok(line), Ok(column)) =
+				(frames.last_mut(), line.parse(), column.parse())
+			{
+				frame.location = Some((file.to_owned(), line, column));
+			}
+		} else if let Some((index, name)) = line.split_once(": ") {
+			if index.chars().all(|char| char.is_ascii_digit()) {
+				frames.push(Frame {
+					name: name.to_owned(),
+					location: None,
+				});
+			}
+		}
+	}
+
+	frames
+}
+
+/// Captures the backtrace of the current thread, leaving out the frames that don't
+/// belong to the program, and adding a warning to `warnings` if it couldn't be captured
+/// in full.
+pub(crate) fn capture_trace(manifest_dir: &str, warnings: &mut Vec<String>) -> Trace {
+	let backtrace = Backtrace::force_capture();
+
+	if backtrace.status() != BacktraceStatus::Captured {
+		warnings.push("Backtraces are not supported on this platform.".into());
+
+		return Trace::default();
+	}
+
+	let frames = parse_frames(&backtrace.to_string());
+
+	if frames.iter().all(|frame| frame.location.is_none()) {
+		warnings.push("This program was compiled without debug symbols.".into());
+	}
+
+	let layers = frames
+		.into_iter()
+		.filter(|frame| {
+			!IGNORED_FRAMES
+				.iter()
+				.any(|ignored| frame.name.starts_with(ignored))
+		})
+		.filter_map(|frame| {
+			let (file, line, column) = frame.location?;
+
+			// the standard library is built elsewhere, so its source is never available.
+			if file.starts_with("/rustc/") {
+				return None;
+			}
+
+			let code = read_source(&file, manifest_dir)
+				.and_then(|lines| lines.get(line.saturating_sub(1) as usize).cloned())
+				.unwrap_or_default();
+
+			Some(Layer {
+				line_number: line as i32,
+				column_number: column as i32,
+				code: code.trim().to_owned(),
+				name: frame.name,
+				file_path: Some(file.strip_prefix("./").unwrap_or(&file).to_owned()),
+			})
+		})
+		.collect();
+
+	Trace { layers }
+}
+
+/// Reads the lines of source either side of `location`, adding a warning to `warnings`
+/// if the source couldn't be read.
+pub(crate) fn capture_snippet(
+	location: &Location<'_>,
+	warnings: &mut Vec<String>,
+) -> BTreeMap<i32, String> {
+	let Some(lines) = read_source(location.file, location.manifest_dir) else {
+		warnings.push(format!(
+			"The source of {} could not be read, so no snippet was captured.",
+			location.file
+		));
+
+		return BTreeMap::new();
+	};
+
+	let first = location.line.saturating_sub(SNIPPET_CONTEXT).max(1);
+	let last = location.line + SNIPPET_CONTEXT;
+
+	(first..=last)
+		.filter_map(|line| {
+			let code = lines.get(line as usize - 1)?;
+
+			Some((line as i32, code.clone()))
+		})
+		.collect()
+}
+
+/// Builds the [`LogBody`] of a `message` of type `message_type` logged at `location`.
+pub fn capture(message: String, message_type: &str, location: &Location<'_>) -> LogBody {
+	let mut warnings = Vec::new();
+	let backtrace = capture_trace(location.manifest_dir, &mut warnings);
+	let snippet = capture_snippet(location, &mut warnings);
+
+	LogBody {
+		message,
+		message_type: message_type.to_owned(),
+		language: "Rust".into(),
+		backtrace,
+		snippet,
+		line_number: location.line as i32,
+		file_name: location.file.to_owned(),
+		warnings,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn frame(name: &str, location: Option<(&str, u32, u32)>) -> Frame {
+		Frame {
+			name: name.to_owned(),
+			location: location.map(|(file, line, column)| (file.to_owned(), line, column)),
+		}
+	}
+
+	#[test]
+	fn parses_frames_with_and_without_locations() {
+		let backtrace = "   0: std::backtrace::Backtrace::force_capture
+             at /rustc/abc123/library/std/src/backtrace.rs:312:13
+   1: my_crate::handler::{{closure}}
+             at ./src/handler.rs:10:5
+   2: my_crate::main
+             at ./src/main.rs:4:5
+   3: __libc_start_main
+   4: _start";
+
+		assert_eq!(
+			parse_frames(backtrace),
+			vec![
+				frame(
+					"std::backtrace::Backtrace::force_capture",
+					Some(("/rustc/abc123/library/std/src/backtrace.rs", 312, 13)),
+				),
+				frame(
+					"my_crate::handler::{{closure}}",
+					Some(("./src/handler.rs", 10, 5))
+				),
+				frame("my_crate::main", Some(("./src/main.rs", 4, 5))),
+				frame("__libc_start_main", None),
+				frame("_start", None),
+			]
+		);
+	}
+
+	#[test]
+	fn keeps_colons_in_file_paths() {
+		let backtrace = "   0: my_crate::main
+             at C:\\Users\\me\\my_crate\\src\\main.rs:7:9";
+
+		assert_eq!(
+			parse_frames(backtrace),
+			vec![frame(
+				"my_crate::main",
+				Some(("C:\\Users\\me\\my_crate\\src\\main.rs", 7, 9))
+			)]
+		);
+	}
+
+	#[test]
+	fn skips_lines_that_are_not_frames() {
+		let backtrace = "note: Some details are omitted
+   0: my_crate::main
+             at ./src/main.rs:not-a-line:5
+             at ./src/main.rs
+   1: my_crate::run";
+
+		assert_eq!(
+			parse_frames(backtrace),
+			vec![frame("my_crate::main", None), frame("my_crate::run", None)]
+		);
+	}
+
+	#[test]
+	fn ignores_locations_before_the_first_frame() {
+		assert!(parse_frames("             at ./src/main.rs:1:1").is_empty());
+	}
+}
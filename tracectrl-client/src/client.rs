@@ -0,0 +1,617 @@
+//! The [`Client`] that sends logs to the server from a background thread, so that
+//! logging never waits on the network.
+
+use std::{
+	collections::VecDeque,
+	fmt,
+	path::PathBuf,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		mpsc as std_mpsc,
+		Arc,
+		Mutex,
+		MutexGuard,
+		OnceLock,
+		PoisonError,
+	},
+	time::Duration,
+};
+
+use reqwest::{header, StatusCode};
+use tokio::sync::{
+	mpsc::{self, error::TrySendError},
+	Notify,
+};
+use uuid::Uuid;
+
+use crate::{
+	cache::{self, Credentials},
+	error::Error,
+	types::LogBody,
+};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_RETRIES: u32 = 5;
+/// The delay before the first retry of a request, which doubles with each retry.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// The most logs kept while the server can't be reached, after which the oldest logs
+/// are dropped.
+const MAX_QUEUED: usize = 10_000;
+/// The most flushes and logs sent on their own that can be waiting for the sending
+/// thread at once.
+const MAX_COMMANDS: usize = 16;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a request can take, including the time taken to connect, before it fails
+/// and is retried.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The name of the thread logs are sent from.
+pub(crate) const WORKER_THREAD: &str = "tracectrl-client";
+
+static GLOBAL: OnceLock<Client> = OnceLock::new();
+
+/// Returns the client installed with [`ClientBuilder::install`], if there is one.
+pub fn global() -> Option<&'static Client> {
+	GLOBAL.get()
+}
+
+enum Command {
+	/// Send every queued log, then answer whether they were all sent.
+	Flush(std_mpsc::SyncSender<bool>),
+	/// Send every queued log, then send a log on its own and answer whether it was sent.
+	LogNow(Box<LogBody>, std_mpsc::SyncSender<bool>),
+}
+
+/// The logs waiting to be sent, shared between every [`Client`] and the sending thread.
+/// Once [`MAX_QUEUED`] logs are waiting, the oldest are dropped to make room for new
+/// ones, so that logging never blocks, and the queue doesn't grow without bound while
+/// the server can't be reached.
+#[derive(Debug)]
+struct Queue {
+	logs: Mutex<VecDeque<LogBody>>,
+	batch_size: usize,
+	/// Woken once a full batch is waiting to be sent.
+	ready: Notify,
+	/// The number of logs dropped since the sending thread last reported it.
+	dropped: AtomicUsize,
+}
+
+impl Queue {
+	fn lock(&self) -> MutexGuard<'_, VecDeque<LogBody>> {
+		self.logs.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Drops the oldest logs in `logs` until there are at most [`MAX_QUEUED`].
+	fn truncate(&self, logs: &mut VecDeque<LogBody>) {
+		let excess = logs.len().saturating_sub(MAX_QUEUED);
+
+		if excess > 0 {
+			logs.drain(..excess);
+			self.dropped.fetch_add(excess, Ordering::Relaxed);
+		}
+	}
+
+	fn push(&self, log: LogBody) {
+		let len = {
+			let mut logs = self.lock();
+			logs.push_back(log);
+			self.truncate(&mut logs);
+
+			logs.len()
+		};
+
+		if len >= self.batch_size {
+			self.ready.notify_one();
+		}
+	}
+
+	/// Takes the oldest batch of logs out of the queue.
+	fn take_batch(&self) -> Vec<LogBody> {
+		let mut logs = self.lock();
+		let size = self.batch_size.min(logs.len());
+
+		logs.drain(..size).collect()
+	}
+
+	/// Puts a `batch` that couldn't be sent back at the front of the queue, to be sent
+	/// again before any newer logs.
+	fn requeue(&self, batch: Vec<LogBody>) {
+		let mut logs = self.lock();
+
+		for log in batch.into_iter().rev() {
+			logs.push_front(log);
+		}
+
+		self.truncate(&mut logs);
+	}
+
+	fn is_empty(&self) -> bool {
+		self.lock().is_empty()
+	}
+
+	fn clear(&self) {
+		self.lock().clear();
+	}
+}
+
+/// Called with the errors that stop logs from being sent, set with
+/// [`ClientBuilder::on_error`].
+#[derive(Clone)]
+struct ErrorHandler(Arc<dyn Fn(&Error) + Send + Sync>);
+
+impl fmt::Debug for ErrorHandler {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("ErrorHandler")
+	}
+}
+
+/// Passes `err` to `handler`, or records it along with `context` as a `tracing` event or
+/// a `log` record if there isn't one. Those made on the sending thread are never sent to
+/// the server themselves.
+fn report(handler: Option<&ErrorHandler>, context: &str, err: &Error) {
+	if let Some(ErrorHandler(handler)) = handler {
+		handler(err);
+		return;
+	}
+
+	#[cfg(feature = "tracing")]
+	if tracing::dispatcher::has_been_set() {
+		tracing::warn!("{context}: {err}");
+		return;
+	}
+
+	#[cfg(feature = "log")]
+	log::warn!("{context}: {err}");
+
+	#[cfg(not(feature = "log"))]
+	let _ = (context, err);
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct RegisterClientResponse {
+	client_id: Uuid,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct NewApiKey {
+	key: String,
+}
+
+/// Configures a [`Client`], created with [`Client::builder`].
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+	server: String,
+	api_key: Option<String>,
+	cache_path: Option<PathBuf>,
+	batch_size: usize,
+	flush_interval: Duration,
+	max_retries: u32,
+	on_error: Option<ErrorHandler>,
+}
+
+impl ClientBuilder {
+	/// The API key to send logs with, whose logs belong to the client the key was
+	/// created for. Without one, the client registers itself and creates its own key,
+	/// which only works for clients that aren't in a project and have never had a key.
+	/// Once the server rejects the key, for example because it was revoked, the client
+	/// stops sending logs until it is given a new key here.
+	#[must_use]
+	pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+		self.api_key = Some(api_key.into());
+		self
+	}
+
+	/// Where the ID and API key of the client are cached, which defaults to a file named
+	/// after the program in `$XDG_CACHE_HOME/tracectrl-client`, or in
+	/// `~/.cache/tracectrl-client`. The file is only readable by the current user, and
+	/// isn't used if it belongs to anyone else.
+	#[must_use]
+	pub fn cache_path(mut self, cache_path: impl Into<PathBuf>) -> Self {
+		self.cache_path = Some(cache_path.into());
+		self
+	}
+
+	/// The most logs sent in a single request, defaults to 100.
+	#[must_use]
+	pub fn batch_size(mut self, batch_size: usize) -> Self {
+		self.batch_size = batch_size.max(1);
+		self
+	}
+
+	/// How long logs are held for before being sent if a batch doesn't fill up, defaults
+	/// to a second.
+	#[must_use]
+	pub fn flush_interval(mut self, flush_interval: Duration) -> Self {
+		self.flush_interval = flush_interval;
+		self
+	}
+
+	/// How many times a request that failed is retried before its logs are dropped,
+	/// defaults to 5.
+	#[must_use]
+	pub fn max_retries(mut self, max_retries: u32) -> Self {
+		self.max_retries = max_retries;
+		self
+	}
+
+	/// Calls `handler` with the errors that stop logs from being sent, such as the server
+	/// being unreachable or rejecting a batch. Without a handler, these are recorded as
+	/// `tracing` events, or as `log` records if no `tracing` subscriber is set, at the
+	/// warning level.
+	#[must_use]
+	pub fn on_error(mut self, handler: impl Fn(&Error) + Send + Sync + 'static) -> Self {
+		self.on_error = Some(ErrorHandler(Arc::new(handler)));
+		self
+	}
+
+	/// Creates the client, starting the thread that sends its logs.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the HTTP client or the sending thread couldn't be created.
+	pub fn build(self) -> Result<Client, Error> {
+		let (sender, receiver) = mpsc::channel(MAX_COMMANDS);
+		let http = reqwest::Client::builder()
+			.connect_timeout(CONNECT_TIMEOUT)
+			.timeout(REQUEST_TIMEOUT)
+			.build()?;
+
+		let cache_path = self.cache_path.clone().unwrap_or_else(cache::default_path);
+
+		let on_error = self.on_error.clone();
+		let queue = Arc::new(Queue {
+			logs: Mutex::default(),
+			batch_size: self.batch_size,
+			ready: Notify::new(),
+			dropped: AtomicUsize::new(0),
+		});
+		let worker = Worker {
+			http,
+			api_key: self.api_key.clone(),
+			config: self,
+			cache_path,
+			queue: Arc::clone(&queue),
+			stopped: false,
+		};
+
+		// the runtime's blocking threads share the name of the sending thread, so that
+		// the events logged while sending can be told apart from the program's own.
+		let runtime = tokio::runtime::Builder::new_current_thread()
+			.thread_name(WORKER_THREAD)
+			.enable_all()
+			.build()?;
+
+		std::thread::Builder::new()
+			.name(WORKER_THREAD.into())
+			.spawn(move || runtime.block_on(worker.run(receiver)))?;
+
+		Ok(Client {
+			queue,
+			sender,
+			on_error,
+		})
+	}
+
+	/// Creates the client and installs it as the client used by the
+	/// [`log!`](crate::log) macro.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the client couldn't be created, or if a client was already
+	/// installed.
+	pub fn install(self) -> Result<&'static Client, Error> {
+		let client = self.build()?;
+
+		GLOBAL.set(client).map_err(|_| Error::AlreadyInstalled)?;
+
+		GLOBAL.get().ok_or(Error::AlreadyInstalled)
+	}
+}
+
+/// Sends logs to a TraceCTRL server. Logs are queued and sent in batches from a
+/// background thread, so sending never blocks. Cloning the client is cheap, and every
+/// clone sends through the same thread.
+#[derive(Debug, Clone)]
+pub struct Client {
+	queue: Arc<Queue>,
+	sender: mpsc::Sender<Command>,
+	on_error: Option<ErrorHandler>,
+}
+
+impl Client {
+	/// Starts configuring a client that sends logs to the server at `server`, such as
+	/// `http://localhost:3000`.
+	pub fn builder(server: impl Into<String>) -> ClientBuilder {
+		ClientBuilder {
+			server: server.into().trim_end_matches('/').to_owned(),
+			api_key: None,
+			cache_path: None,
+			batch_size: DEFAULT_BATCH_SIZE,
+			flush_interval: DEFAULT_FLUSH_INTERVAL,
+			max_retries: DEFAULT_MAX_RETRIES,
+			on_error: None,
+		}
+	}
+
+	/// Queues `log` to be sent with the next batch. If 10,000 logs are already waiting to
+	/// be sent, the oldest of them is dropped.
+	pub fn send(&self, log: LogBody) {
+		self.queue.push(log);
+	}
+
+	/// Blocks until every log queued so far has been sent, or `timeout` has passed,
+	/// returning whether they were all sent. Logs still queued when the program exits are
+	/// lost, so this should be called before exiting.
+	pub fn flush(&self, timeout: Duration) -> bool {
+		let (done, wait) = std_mpsc::sync_channel(1);
+
+		self.command(Command::Flush(done)) && wait.recv_timeout(timeout).unwrap_or(false)
+	}
+
+	/// Sends `log` on its own through `/api/log` after every log queued so far, blocking
+	/// until it has been sent or `timeout` has passed, and returning whether it was sent.
+	pub fn send_now(&self, log: LogBody, timeout: Duration) -> bool {
+		let (done, wait) = std_mpsc::sync_channel(1);
+
+		self.command(Command::LogNow(Box::new(log), done))
+			&& wait.recv_timeout(timeout).unwrap_or(false)
+	}
+
+	/// Passes `command` to the sending thread without blocking, returning whether it was
+	/// passed on.
+	fn command(&self, command: Command) -> bool {
+		match self.sender.try_send(command) {
+			Ok(()) => true,
+			Err(TrySendError::Full(_)) => {
+				self.report(
+					"Could not reach the sending thread",
+					&Error::Busy(MAX_COMMANDS),
+				);
+				false
+			}
+			// the thread only stops once every client is dropped, or the API key was
+			// rejected, which has been reported already.
+			Err(TrySendError::Closed(_)) => false,
+		}
+	}
+
+	/// Passes `err` to the handler set with [`ClientBuilder::on_error`], or records it
+	/// along with `context` if there isn't one.
+	pub(crate) fn report(&self, context: &str, err: &Error) {
+		report(self.on_error.as_ref(), context, err);
+	}
+}
+
+/// Sends the logs queued by every [`Client`] created from the same builder.
+struct Worker {
+	http: reqwest::Client,
+	config: ClientBuilder,
+	cache_path: PathBuf,
+	/// The API key logs are sent with, once it is known.
+	api_key: Option<String>,
+	queue: Arc<Queue>,
+	/// Whether the server rejected the API key, after which nothing more is sent.
+	stopped: bool,
+}
+
+impl Worker {
+	fn report(&self, context: &str, err: &Error) {
+		report(self.config.on_error.as_ref(), context, err);
+	}
+
+	/// Stops sending logs for good, dropping those still queued, as the server rejected
+	/// the API key with `err`.
+	fn stop(&mut self, err: &Error) {
+		self.report(
+			"The API key was rejected, so no more logs will be sent until the client is \
+			 given a valid key with `ClientBuilder::api_key`",
+			err,
+		);
+
+		self.queue.clear();
+		self.stopped = true;
+	}
+
+	async fn run(mut self, mut receiver: mpsc::Receiver<Command>) {
+		let mut interval = tokio::time::interval(self.config.flush_interval);
+		let queue = Arc::clone(&self.queue);
+
+		while !self.stopped {
+			tokio::select! {
+				command = receiver.recv() => match command {
+					Some(Command::Flush(done)) => {
+						self.send_queue().await;
+						let _ = done.send(!self.stopped && self.queue.is_empty());
+					}
+					Some(Command::LogNow(log, done)) => {
+						self.send_queue().await;
+
+						let sent = match self.post("/api/log", &*log).await {
+							Ok(()) => true,
+							Err(err) if is_unauthorized(&err) => {
+								self.stop(&err);
+								false
+							}
+							Err(err) => {
+								self.report("Could not send log", &err);
+								false
+							}
+						};
+
+						let _ = done.send(sent);
+					}
+					None => {
+						self.send_queue().await;
+						return;
+					}
+				},
+				() = queue.ready.notified() => self.send_queue().await,
+				_ = interval.tick() => self.send_queue().await,
+			}
+		}
+	}
+
+	/// Sends every queued log in batches, putting any batch that couldn't be sent back in
+	/// the queue so that it is tried again later.
+	async fn send_queue(&mut self) {
+		let dropped = self.queue.dropped.swap(0, Ordering::Relaxed);
+
+		if dropped > 0 {
+			self.report("Logs were dropped", &Error::QueueFull(dropped));
+		}
+
+		loop {
+			let batch = self.queue.take_batch();
+
+			if batch.is_empty() {
+				return;
+			}
+
+			match self.post("/api/logs/batch", &batch).await {
+				Ok(()) => {}
+				Err(err) if is_unauthorized(&err) => {
+					self.stop(&err);
+					return;
+				}
+				Err(err) if is_permanent(&err) => {
+					let size = batch.len();
+					self.report(&format!("Dropping {size} logs the server rejected"), &err);
+				}
+				Err(err) => {
+					self.report("Could not send logs, will try again", &err);
+					self.queue.requeue(batch);
+					return;
+				}
+			}
+		}
+	}
+
+	/// Sends `body` to the endpoint at `path`, retrying with an increasing delay if the
+	/// server can't be reached.
+	async fn post(
+		&mut self,
+		path: &str,
+		body: &impl serde::Serialize,
+	) -> Result<(), Error> {
+		let body = serde_json::to_vec(body)?;
+		let mut backoff = INITIAL_BACKOFF;
+		let mut attempt = 0;
+
+		loop {
+			let result = match self.api_key().await {
+				Ok(api_key) => {
+					let response = self
+						.http
+						.post(format!("{}{path}", self.config.server))
+						.bearer_auth(&api_key)
+						.header(header::CONTENT_TYPE, "application/json")
+						.body(body.clone())
+						.send()
+						.await;
+
+					match response {
+						Ok(response) => check(response).await.map(|_| ()),
+						Err(err) => Err(err.into()),
+					}
+				}
+				Err(err) => Err(err),
+			};
+
+			match result {
+				Err(err) if !is_permanent(&err) && attempt < self.config.max_retries => {}
+				result => return result,
+			}
+
+			attempt += 1;
+			tokio::time::sleep(backoff).await;
+			backoff = (backoff * 2).min(MAX_BACKOFF);
+		}
+	}
+
+	/// Returns the API key to send logs with. Unless the client was given one, it is
+	/// registered and an API key is created for it if needed.
+	async fn api_key(&mut self) -> Result<String, Error> {
+		// the server knows which client a key belongs to, so a client that was given one
+		// is never registered.
+		if let Some(api_key) = &self.api_key {
+			return Ok(api_key.clone());
+		}
+
+		let server = self.config.server.clone();
+		let cached = cache::read(&self.cache_path)
+			.unwrap_or_else(|err| {
+				self.report("Could not read cached client credentials", &err);
+				None
+			})
+			.filter(|cached| cached.server == server);
+
+		// registering with the cached ID returns the same client if it still exists.
+		let register_url = match &cached {
+			Some(cached) => format!("{server}/api/get_or_register_client/{}", cached.client_id),
+			None => format!("{server}/api/get_or_register_client"),
+		};
+		let response = check(self.http.post(register_url).send().await?).await?;
+		let RegisterClientResponse { client_id } = response.json().await?;
+
+		let api_key = match cached {
+			Some(cached) if cached.client_id == client_id => cached.api_key,
+			_ => {
+				let response = self
+					.http
+					.post(format!("{server}/api/clients/{client_id}/keys"))
+					.send()
+					.await?;
+				let NewApiKey { key } = check(response).await?.json().await?;
+
+				key
+			}
+		};
+
+		let credentials = Credentials {
+			server,
+			client_id,
+			api_key,
+		};
+
+		if let Err(err) = cache::write(&self.cache_path, &credentials) {
+			self.report("Could not cache client credentials", &err);
+		}
+
+		self.api_key = Some(credentials.api_key.clone());
+
+		Ok(credentials.api_key)
+	}
+}
+
+/// Turns responses with an error status into an [`Error::ResponseError`].
+async fn check(response: reqwest::Response) -> Result<reqwest::Response, Error> {
+	let status = response.status();
+
+	if status.is_success() {
+		return Ok(response);
+	}
+
+	let message = response.text().await.unwrap_or_default();
+
+	Err(Error::ResponseError(status, message))
+}
+
+/// Whether the server rejected the API key, or refused to create one. A key that was
+/// revoked is never replaced by the client itself, so that revoking it locks the
+/// client out.
+fn is_unauthorized(err: &Error) -> bool {
+	matches!(err, Error::ResponseError(StatusCode::UNAUTHORIZED, _))
+}
+
+/// Whether sending again can't succeed, because the server rejected the request itself.
+fn is_permanent(err: &Error) -> bool {
+	match err {
+		Error::ResponseError(status, _) => {
+			status.is_client_error() && *status != StatusCode::TOO_MANY_REQUESTS
+		}
+		Error::SerdeJSONError(_) => true,
+		_ => false,
+	}
+}
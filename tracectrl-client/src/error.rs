@@ -0,0 +1,30 @@
+//! Contains the [`enum@Error`] type returned by the client.
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+	#[error("a client has already been installed")]
+	AlreadyInstalled,
+	#[error("status code {0}, error: {1}")]
+	ResponseError(StatusCode, String),
+	#[error("the log could not be sent before the timeout, or was rejected")]
+	NotSent,
+	#[error("{0} logs were dropped, as too many were waiting to be sent")]
+	QueueFull(usize),
+	#[error("{0} requests were already waiting for the sending thread")]
+	Busy(usize),
+	#[error("{} is not a file owned by the current user that only they can write to", .0.display())]
+	UntrustedCache(std::path::PathBuf),
+
+	#[error(transparent)]
+	IoError(#[from] std::io::Error),
+	#[error(transparent)]
+	ReqwestError(#[from] reqwest::Error),
+	#[error(transparent)]
+	SerdeJSONError(#[from] serde_json::Error),
+	#[cfg(feature = "log")]
+	#[error(transparent)]
+	SetLoggerError(#[from] log::SetLoggerError),
+}
@@ -0,0 +1,124 @@
+//! Sends logs from Rust programs to a TraceCTRL server.
+//!
+//! Once a [`Client`] is installed, the [`log!`] macro captures everything the server
+//! shows about a log: the file and line it was made on, the source around it, a
+//! backtrace, and the type of the value that was logged.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use tracectrl_client::{log, Client};
+//!
+//! let client = Client::builder("http://localhost:3000").install().unwrap();
+//!
+//! log!("hello");
+//! log!("{} + {} = {}", 1, 2, 1 + 2);
+//!
+//! // logs are sent in the background, so they are flushed before exiting.
+//! client.flush(Duration::from_secs(5));
+//! ```
+//!
+//! The client registers itself through `/api/get_or_register_client` the first time it
+//! sends a log, and caches its ID and API key so that the program is the same client
+//! every time it runs.
+//!
+//! With the `tracing` feature, which is enabled by default, [`Client::layer`] creates a
+//! [`TraceCtrlLayer`] that sends the events of the `tracing` crate as logs too. With the
+//! `log` feature, also enabled by default, [`Client::logger`] creates a
+//! [`TraceCtrlLogger`] that does the same for the `log` crate. [`install_panic_hook`]
+//! sends panics before the program exits.
+
+#![warn(clippy::pedantic)]
+#![allow(
+	clippy::module_name_repetitions,
+	clippy::doc_markdown,
+	clippy::must_use_candidate,
+	clippy::cast_possible_truncation,
+	clippy::cast_possible_wrap,
+	clippy::cast_sign_loss
+)]
+
+mod cache;
+mod capture;
+mod client;
+mod error;
+#[cfg(feature = "log")]
+mod logger;
+mod panic;
+#[cfg(feature = "tracing")]
+mod subscriber;
+mod types;
+
+pub use client::{Client, ClientBuilder};
+pub use error::Error;
+#[cfg(feature = "log")]
+pub use logger::TraceCtrlLogger;
+pub use panic::install_panic_hook;
+#[cfg(feature = "tracing")]
+pub use subscriber::TraceCtrlLayer;
+pub use types::{Layer, LogBody, Trace};
+
+/// Used by the [`log!`] macro, and not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+	pub use tracectrl_client_macros::literal_message;
+
+	pub use crate::capture::{capture, Location};
+
+	/// Sends `log` with the installed client, doing nothing if there isn't one.
+	pub fn send(log: crate::LogBody) {
+		if let Some(client) = crate::client::global() {
+			client.send(log);
+		}
+	}
+}
+
+/// Logs a value to the installed [`Client`], capturing the file and line it was logged
+/// from, the source around it, and a backtrace. The value is sent as its
+/// [`Display`](std::fmt::Display) output, and its type is sent as the message type.
+/// Given a format string instead, with or without arguments, the formatted string is
+/// sent.
+///
+/// Nothing is sent if no client has been installed with [`ClientBuilder::install`].
+///
+/// ```no_run
+/// # use tracectrl_client::log;
+/// let answer = 42;
+///
+/// log!(answer);
+/// log!(42);
+/// log!("the answer is {answer}");
+/// ```
+#[macro_export]
+macro_rules! log {
+	(@send $message:expr, $type_name:expr) => {
+		$crate::__private::send($crate::__private::capture(
+			$message,
+			$type_name,
+			&$crate::__private::Location {
+				file: ::std::file!(),
+				line: ::std::line!(),
+				manifest_dir: ::std::env!("CARGO_MANIFEST_DIR"),
+			},
+		))
+	};
+	// only string literals are formatted, but which kind of literal was given can't be
+	// told apart here.
+	($literal:literal $(,)?) => {
+		$crate::log!(
+			@send $crate::__private::literal_message!($literal),
+			::std::any::type_name_of_val(&$literal)
+		)
+	};
+	($message:expr $(,)?) => {{
+		let message = &$message;
+
+		$crate::log!(
+			@send ::std::string::ToString::to_string(message),
+			::std::any::type_name_of_val(message)
+		)
+	}};
+	($format:literal, $($arg:tt)+) => {
+		$crate::log!(::std::format!($format, $($arg)+))
+	};
+}
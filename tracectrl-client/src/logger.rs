@@ -0,0 +1,144 @@
+//! A [`log::Log`] implementation that sends the records of the `log` crate to a
+//! TraceCTRL server, enabled by the `log` feature.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use log::{LevelFilter, Metadata, Record};
+
+use crate::{
+	capture::{capture_snippet, Location},
+	client::{Client, WORKER_THREAD},
+	error::Error,
+	types::{Layer, LogBody, Trace},
+};
+
+/// How long [`log::logger().flush()`](log::Log::flush) waits for records to be sent.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends the records of the `log` crate to a TraceCTRL server, created with
+/// [`Client::logger`].
+///
+/// Each record is sent with its level as the message type, and its module as the
+/// backtrace. Records are queued on the [`Client`] and sent in batches, so logging
+/// never waits on the network.
+///
+/// ```no_run
+/// use tracectrl_client::Client;
+///
+/// let client = Client::builder("http://localhost:3000").build().unwrap();
+///
+/// client.logger().install().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TraceCtrlLogger {
+	client: Client,
+	max_level: LevelFilter,
+}
+
+impl TraceCtrlLogger {
+	/// The most verbose level of the records that are sent, defaults to
+	/// [`LevelFilter::Info`].
+	#[must_use]
+	pub fn with_max_level(mut self, max_level: LevelFilter) -> Self {
+		self.max_level = max_level;
+		self
+	}
+
+	/// Installs the logger as the logger of the `log` crate, and sets the most verbose
+	/// level that is logged to its own.
+	///
+	/// # Errors
+	///
+	/// Returns an error if a logger was already installed.
+	pub fn install(self) -> Result<(), Error> {
+		let max_level = self.max_level;
+
+		log::set_boxed_logger(Box::new(self))?;
+		log::set_max_level(max_level);
+
+		Ok(())
+	}
+}
+
+impl Client {
+	/// Creates a [`TraceCtrlLogger`] that sends the records of the `log` crate with this
+	/// client.
+	pub fn logger(&self) -> TraceCtrlLogger {
+		TraceCtrlLogger {
+			client: self.clone(),
+			max_level: LevelFilter::Info,
+		}
+	}
+}
+
+impl log::Log for TraceCtrlLogger {
+	fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+		metadata.level() <= self.max_level
+	}
+
+	fn log(&self, record: &Record<'_>) {
+		if !self.enabled(record.metadata()) {
+			return;
+		}
+
+		// sending logs makes records of its own, which would otherwise be sent forever.
+		if std::thread::current().name() == Some(WORKER_THREAD) {
+			return;
+		}
+
+		let mut warnings = Vec::new();
+
+		let snippet = if let (Some(file), Some(line)) = (record.file(), record.line()) {
+			capture_snippet(
+				&Location {
+					file,
+					line,
+					manifest_dir: "",
+				},
+				&mut warnings,
+			)
+		} else {
+			warnings
+				.push("The record has no source location, so no snippet was captured.".into());
+
+			BTreeMap::new()
+		};
+
+		let line_number = record.line().unwrap_or_default() as i32;
+		let code = snippet.get(&line_number).map(|code| code.trim().to_owned());
+
+		let layer = Layer {
+			line_number,
+			column_number: 0,
+			code: code.unwrap_or_default(),
+			name: record.module_path().unwrap_or(record.target()).to_owned(),
+			file_path: record.file().map(str::to_owned),
+		};
+
+		// the target is only worth sending when it was set to something other than the
+		// module the record was made in.
+		let message = match record.module_path() {
+			Some(module_path) if module_path != record.target() => {
+				format!("[{}] {}", record.target(), record.args())
+			}
+			_ => record.args().to_string(),
+		};
+
+		self.client.send(LogBody {
+			message,
+			message_type: record.level().to_string(),
+			language: "Rust".into(),
+			backtrace: Trace {
+				layers: vec![layer],
+			},
+			snippet,
+			line_number,
+			file_name: record.file().unwrap_or(record.target()).to_owned(),
+			warnings,
+		});
+	}
+
+	fn flush(&self) {
+		self.client.flush(FLUSH_TIMEOUT);
+	}
+}
@@ -0,0 +1,104 @@
+//! A panic hook that sends panics to a TraceCTRL server before the program exits.
+
+use std::{
+	collections::BTreeMap,
+	panic::{self, PanicHookInfo},
+	time::Duration,
+};
+
+use crate::{
+	capture::{capture_snippet, capture_trace, Location},
+	client::{Client, WORKER_THREAD},
+	error::Error,
+	types::LogBody,
+};
+
+/// How long a panicking thread waits for its panic to be sent.
+const PANIC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Installs a panic hook that sends every panic to the server as a log with the
+/// message type `panic`, capturing its payload, location, thread and backtrace.
+///
+/// The panicking thread blocks until the panic has been sent, or for at most 5
+/// seconds, so that the panic isn't lost when the program exits. The hook that was
+/// installed before, which prints the panic by default, still runs first.
+///
+/// ```no_run
+/// use tracectrl_client::Client;
+///
+/// let client = Client::builder("http://localhost:3000").build().unwrap();
+///
+/// tracectrl_client::install_panic_hook(&client);
+/// ```
+pub fn install_panic_hook(client: &Client) {
+	let client = client.clone();
+	let previous = panic::take_hook();
+
+	panic::set_hook(Box::new(move |info| {
+		previous(info);
+
+		let thread = std::thread::current();
+
+		// the thread that would send the panic is the one that panicked.
+		if thread.name() == Some(WORKER_THREAD) {
+			return;
+		}
+
+		let log = capture_panic(info, thread.name().unwrap_or("<unnamed>"));
+
+		if !client.send_now(log, PANIC_TIMEOUT) {
+			client.report("Could not send the panic to the server", &Error::NotSent);
+		}
+	}));
+}
+
+/// Builds the [`LogBody`] of the panic described by `info`, which happened on the
+/// thread named `thread`.
+fn capture_panic(info: &PanicHookInfo<'_>, thread: &str) -> LogBody {
+	let payload = info
+		.payload()
+		.downcast_ref::<&str>()
+		.copied()
+		.or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+		.unwrap_or("Box<dyn Any>");
+
+	let mut warnings = Vec::new();
+	let backtrace = capture_trace("", &mut warnings);
+
+	let (message, snippet) = if let Some(location) = info.location() {
+		let snippet = capture_snippet(
+			&Location {
+				file: location.file(),
+				line: location.line(),
+				manifest_dir: "",
+			},
+			&mut warnings,
+		);
+
+		(
+			format!("thread '{thread}' panicked at {location}:\n{payload}"),
+			snippet,
+		)
+	} else {
+		warnings.push("The panic has no location, so no snippet was captured.".into());
+
+		(
+			format!("thread '{thread}' panicked:\n{payload}"),
+			BTreeMap::new(),
+		)
+	};
+
+	LogBody {
+		message,
+		message_type: "panic".into(),
+		language: "Rust".into(),
+		backtrace,
+		snippet,
+		line_number: info.location().map_or(0, |location| location.line() as i32),
+		file_name: info
+			.location()
+			.map(|location| location.file().to_owned())
+			.unwrap_or_default(),
+		warnings,
+	}
+}
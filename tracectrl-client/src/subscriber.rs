@@ -0,0 +1,216 @@
+//! A [`tracing_subscriber::Layer`] that sends the events of [`tracing`] to a TraceCTRL
+//! server, enabled by the `tracing` feature.
+
+use std::{
+	collections::BTreeMap,
+	fmt::{self, Write as _},
+};
+
+use tracing::{
+	field::{Field, Visit},
+	span,
+	Event,
+	Subscriber,
+};
+use tracing_subscriber::{filter::LevelFilter, layer::Context, registry::LookupSpan};
+
+use crate::{
+	capture::{capture_snippet, Location},
+	client::{Client, WORKER_THREAD},
+	types::{Layer, LogBody, Trace},
+};
+
+/// Sends [`tracing`] events to a TraceCTRL server, created with [`Client::layer`].
+///
+/// Each event is sent with its level as the message type, its fields after its
+/// message, and the spans it was recorded in as the backtrace, innermost first. Events
+/// are queued on the [`Client`], so recording them never waits on the network.
+///
+/// ```no_run
+/// use tracectrl_client::Client;
+/// use tracing_subscriber::prelude::*;
+///
+/// let client = Client::builder("http://localhost:3000").build().unwrap();
+///
+/// tracing_subscriber::registry().with(client.layer()).init();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TraceCtrlLayer {
+	client: Client,
+	max_level: LevelFilter,
+}
+
+impl TraceCtrlLayer {
+	/// The most verbose level of the events that are sent, defaults to
+	/// [`LevelFilter::INFO`]. This only filters what is sent to the server, so other
+	/// layers still see every event.
+	#[must_use]
+	pub fn with_max_level(mut self, max_level: impl Into<LevelFilter>) -> Self {
+		self.max_level = max_level.into();
+		self
+	}
+}
+
+impl Client {
+	/// Creates a [`TraceCtrlLayer`] that sends [`tracing`] events with this client.
+	pub fn layer(&self) -> TraceCtrlLayer {
+		TraceCtrlLayer {
+			client: self.clone(),
+			max_level: LevelFilter::INFO,
+		}
+	}
+}
+
+/// The fields of a span, formatted as `name=value` pairs, which are kept in the span's
+/// extensions until an event is recorded in it.
+struct SpanFields(String);
+
+/// Collects the fields of an event or span, keeping the `message` field of events apart
+/// from the rest.
+#[derive(Default)]
+struct Fields {
+	message: Option<String>,
+	fields: String,
+}
+
+impl Fields {
+	fn push(&mut self, field: &Field, value: fmt::Arguments) {
+		if !self.fields.is_empty() {
+			self.fields.push(' ');
+		}
+
+		let _ = write!(self.fields, "{}={value}", field.name());
+	}
+}
+
+impl Visit for Fields {
+	fn record_str(&mut self, field: &Field, value: &str) {
+		if field.name() == "message" {
+			self.message = Some(value.to_owned());
+		} else {
+			self.push(field, format_args!("{value:?}"));
+		}
+	}
+
+	fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+		if field.name() == "message" {
+			self.message = Some(format!("{value:?}"));
+		} else {
+			self.push(field, format_args!("{value:?}"));
+		}
+	}
+}
+
+impl<S> tracing_subscriber::Layer<S> for TraceCtrlLayer
+where
+	S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+{
+	fn on_new_span(
+		&self,
+		attrs: &span::Attributes<'_>,
+		id: &span::Id,
+		ctx: Context<'_, S>,
+	) {
+		let Some(span) = ctx.span(id) else {
+			return;
+		};
+
+		let mut fields = Fields::default();
+		attrs.record(&mut fields);
+
+		span.extensions_mut().insert(SpanFields(fields.fields));
+	}
+
+	fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+		let Some(span) = ctx.span(id) else {
+			return;
+		};
+
+		let mut fields = Fields::default();
+		values.record(&mut fields);
+
+		let mut extensions = span.extensions_mut();
+
+		match extensions.get_mut::<SpanFields>() {
+			Some(SpanFields(existing)) if !existing.is_empty() => {
+				existing.push(' ');
+				existing.push_str(&fields.fields);
+			}
+			Some(existing) => existing.0 = fields.fields,
+			None => extensions.insert(SpanFields(fields.fields)),
+		}
+	}
+
+	fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+		let metadata = event.metadata();
+
+		if *metadata.level() > self.max_level {
+			return;
+		}
+
+		// sending logs records events of its own, which would otherwise be sent forever.
+		if std::thread::current().name() == Some(WORKER_THREAD) {
+			return;
+		}
+
+		let mut fields = Fields::default();
+		event.record(&mut fields);
+
+		let message = match (fields.message, fields.fields.is_empty()) {
+			(Some(message), true) => message,
+			(Some(message), false) => format!("{message} {}", fields.fields),
+			(None, _) => fields.fields,
+		};
+
+		let layers = ctx
+			.event_scope(event)
+			.into_iter()
+			.flatten()
+			.map(|span| {
+				let span_metadata = span.metadata();
+				let code = span
+					.extensions()
+					.get::<SpanFields>()
+					.map(|SpanFields(fields)| fields.clone())
+					.unwrap_or_default();
+
+				Layer {
+					line_number: span_metadata.line().unwrap_or_default() as i32,
+					column_number: 0,
+					code,
+					name: format!("{}::{}", span_metadata.target(), span_metadata.name()),
+					file_path: span_metadata.file().map(str::to_owned),
+				}
+			})
+			.collect();
+
+		let mut warnings = Vec::new();
+
+		let snippet = if let (Some(file), Some(line)) = (metadata.file(), metadata.line()) {
+			capture_snippet(
+				&Location {
+					file,
+					line,
+					manifest_dir: "",
+				},
+				&mut warnings,
+			)
+		} else {
+			warnings
+				.push("The event has no source location, so no snippet was captured.".into());
+
+			BTreeMap::new()
+		};
+
+		self.client.send(LogBody {
+			message,
+			message_type: metadata.level().to_string(),
+			language: "Rust".into(),
+			backtrace: Trace { layers },
+			snippet,
+			line_number: metadata.line().unwrap_or_default() as i32,
+			file_name: metadata.file().unwrap_or(metadata.target()).to_owned(),
+			warnings,
+		});
+	}
+}
@@ -0,0 +1,37 @@
+//! The bodies sent to the server, which match the `LogBody`, `Trace` and `Layer`
+//! schemas of the API.
+
+use std::collections::BTreeMap;
+
+/// A log, as sent to `POST /api/log` and `POST /api/logs/batch`.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct LogBody {
+	pub message: String,
+	/// The type of the value that was logged, such as `&str`.
+	pub message_type: String,
+	pub language: String,
+	pub backtrace: Trace,
+	/// The lines of source around the log, keyed by their line number.
+	pub snippet: BTreeMap<i32, String>,
+	pub line_number: i32,
+	pub file_name: String,
+	/// Anything that stopped the log from being captured in full.
+	pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct Trace {
+	pub layers: Vec<Layer>,
+}
+
+/// A single frame of a [`Trace`].
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Layer {
+	pub line_number: i32,
+	pub column_number: i32,
+	/// The line of source at `line_number`, or an empty string if it couldn't be read.
+	pub code: String,
+	/// The name of the function.
+	pub name: String,
+	pub file_path: Option<String>,
+}